@@ -0,0 +1,366 @@
+//! Chains consisting of different devices on the same data line
+//!
+//! A chain is described by a list of [`Segment`]s, each with its own amount of
+//! leds & data layout, e.g. a few sk6812w leds followed by ws2812 leds. All
+//! leds are written with `RGBW` colors, the white channel is ignored for rgb
+//! segments.
+//!
+//! All devices share the same data line, so the [`Timing`] needs to work for
+//! all of them. The bytes of all segments are encoded like ws2812 data (see
+//! [`Encoder::encode_byte`]).
+//!
+//! Segments only support devices with 8 bit rgb or rgbw channels & no header
+//! (see [`Format`]), e.g. ws2812, ws2811, ws2813, sk6812 & sk6812w leds. Other
+//! [`devices`](crate::devices) like the ws2805, ws2816, ucs8904 or tm1814
+//! can't be part of a chain.
+
+use smart_leds_trait::{SmartLedsWrite, RGBW};
+
+use crate::color_order::ColorOrder;
+use crate::encoder::Encoder;
+use crate::spi::{self, MosiIdle};
+use crate::timing::Timing;
+use crate::Error;
+
+/// Data layout of the leds in a segment
+///
+/// Only 8 bit rgb & rgbw layouts are supported, so devices with more channels,
+/// 16 bit channels or a header (see [`Device`](crate::devices::Device)) can't
+/// be used in a chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Three channels, like the ws2812
+    Rgb(ColorOrder),
+    /// Four channels, like the sk6812w
+    Rgbw(ColorOrder),
+}
+
+impl Format {
+    /// Amount of bytes sent per led
+    pub const fn bytes_per_led(&self) -> usize {
+        match self {
+            Format::Rgb(_) => 3,
+            Format::Rgbw(_) => 4,
+        }
+    }
+
+    /// Write the bytes for a single led into `out`, which is exactly
+    /// [`Format::bytes_per_led`] long
+    pub(crate) fn write_led(&self, color: RGBW<u8, u8>, out: &mut [u8]) {
+        let rgb = [color.r, color.g, color.b];
+        match self {
+            Format::Rgb(order) => out.copy_from_slice(&order.arrange_rgb(rgb)),
+            Format::Rgbw(order) => out.copy_from_slice(&order.arrange_rgbw(rgb, color.a.0)),
+        }
+    }
+}
+
+/// Consecutive leds of the same kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    len: usize,
+    format: Format,
+}
+
+impl Segment {
+    pub const fn new(len: usize, format: Format) -> Self {
+        Self { len, format }
+    }
+
+    /// `len` ws2812 leds
+    pub const fn ws2812(len: usize) -> Self {
+        Self::new(len, Format::Rgb(ColorOrder::GRB))
+    }
+
+    /// `len` sk6812w leds
+    pub const fn sk6812w(len: usize) -> Self {
+        Self::new(len, Format::Rgbw(ColorOrder::GRBW))
+    }
+
+    /// Amount of leds
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn format(&self) -> Format {
+        self.format
+    }
+}
+
+/// Amount of bytes needed to render all leds of `segments` with `timing`,
+/// including the reset
+///
+/// The reset is counted twice, if MOSI idles high by default
+/// ([`MosiIdle::DEFAULT`]).
+///
+/// This is the buffer size needed by the
+/// [`prerendered_chain`](crate::prerendered_chain) variant.
+pub const fn buffer_size(segments: &[Segment], timing: &Timing) -> usize {
+    buffer_size_with_idle(segments, timing, MosiIdle::DEFAULT, false)
+}
+
+/// Amount of bytes needed to render all leds of `segments` with `timing`,
+/// for drivers using a different MOSI `idle` level (see `with_mosi_idle`) or
+/// `inverted` output (see `with_inverted_output`)
+pub const fn buffer_size_with_idle(
+    segments: &[Segment],
+    timing: &Timing,
+    idle: MosiIdle,
+    inverted: bool,
+) -> usize {
+    let mut bytes = 0;
+    let mut i = 0;
+    while i < segments.len() {
+        bytes += segments[i].len * segments[i].format.bytes_per_led();
+        i += 1;
+    }
+    bytes * timing.bits() as usize + idle.resets(inverted) * timing.reset_bytes()
+}
+
+pub struct Ws2812<'s, SPI> {
+    spi: SPI,
+    segments: &'s [Segment],
+    encoder: Encoder,
+}
+
+impl<'s, SPI, E> Ws2812<'s, SPI>
+where
+    SPI: spi::Write<Error = E>,
+{
+    /// Use a chain of different devices via spi
+    ///
+    /// The SPI bus should run within 2.3 MHz to 3.8 MHz, if the chain only
+    /// contains ws2812 & sk6812w leds.
+    ///
+    /// Please ensure that the mcu is pretty fast, otherwise weird timing
+    /// issues will occur
+    pub fn new(spi: SPI, segments: &'s [Segment]) -> Self {
+        Self {
+            spi,
+            segments,
+            encoder: Encoder::new(),
+        }
+    }
+
+    /// Use a different spi bit pattern
+    ///
+    /// This is needed if the spi doesn't run within the frequency range
+    /// supported by [`Timing::DEFAULT`].
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.encoder = self.encoder.with_timing(timing);
+        self
+    }
+
+    /// Use a different MOSI idle level
+    ///
+    /// If MOSI idles high, a reset is sent before each frame as well. By
+    /// default this depends on the `mosi_idle_high` feature.
+    pub fn with_mosi_idle(mut self, idle: MosiIdle) -> Self {
+        self.encoder = self.encoder.with_mosi_idle(idle);
+        self
+    }
+
+    /// Invert the output, e.g. for inverting level shifters
+    ///
+    /// Every spi bit is complemented & the reset is sent as high bytes, so
+    /// MOSI should idle high (see [`MosiIdle`]).
+    pub fn with_inverted_output(mut self, inverted: bool) -> Self {
+        self.encoder = self.encoder.with_inverted_output(inverted);
+        self
+    }
+
+    /// Write a single byte for ws2812 devices
+    fn write_byte(&mut self, data: u8) -> Result<(), E> {
+        let mut out = [0; 8];
+        let out = &mut out[..self.encoder.timing().bits() as usize];
+        self.encoder.encode_byte(data, out);
+        self.spi.write(out)
+    }
+
+    fn flush(&mut self) -> Result<(), E> {
+        spi::write_reset(
+            &mut self.spi,
+            self.encoder.reset_size(),
+            self.encoder.reset_level(),
+        )
+    }
+}
+
+impl<'s, SPI, E> SmartLedsWrite for Ws2812<'s, SPI>
+where
+    SPI: spi::Write<Error = E>,
+{
+    type Error = Error<E>;
+    type Color = RGBW<u8, u8>;
+    /// Write all the items of an iterator to the chain
+    ///
+    /// Each segment takes as many items as it has leds. Fails with
+    /// [`Error::TooManyPixels`], if there are more items than leds in all
+    /// segments. As the data is streamed, the leds are still written in this
+    /// case.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        if self.encoder.pre_reset() {
+            self.flush()?;
+        }
+
+        let mut iterator = iterator.into_iter();
+        for segment in self.segments {
+            let format = segment.format;
+            let mut data = [0; 4];
+            let data = &mut data[..format.bytes_per_led()];
+            for item in iterator.by_ref().take(segment.len) {
+                format.write_led(item.into(), data);
+                for byte in data.iter() {
+                    self.write_byte(*byte)?;
+                }
+            }
+        }
+        self.flush()?;
+        self.spi.flush().map_err(Error::Spi)?;
+        match iterator.next() {
+            Some(_) => Err(Error::TooManyPixels),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use smart_leds_trait::{White, RGB8};
+
+    use super::*;
+    use crate::devices::{Device, Sk6812w, Ws2812 as Ws2812Device};
+    use crate::prerendered_chain;
+
+    /// Collects the written spi data
+    struct Spi<'a>(&'a mut Vec<u8>);
+
+    impl spi::Write for Spi<'_> {
+        type Error = ();
+
+        fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    const SEGMENTS: [Segment; 3] = [
+        Segment::sk6812w(2),
+        Segment::ws2812(3),
+        Segment::new(1, Format::Rgb(ColorOrder::BRG)),
+    ];
+
+    fn colors() -> Vec<RGBW<u8, u8>> {
+        (0..6u8)
+            .map(|i| RGBW {
+                r: i,
+                g: 0x10 + i,
+                b: 0x80 + i,
+                a: White(0xf0 + i),
+            })
+            .collect()
+    }
+
+    /// Append the encoded `color` of device `D`
+    fn encode<D: Device>(encoder: Encoder<D>, color: D::Color, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.resize(start + encoder.led_size(), 0);
+        encoder.encode_led(color, &mut out[start..]);
+    }
+
+    /// The frame of the chain, made of the encoded leds of each segment
+    fn expected(idle: MosiIdle, inverted: bool) -> Vec<u8> {
+        let encoder = Encoder::<Ws2812Device>::new()
+            .with_mosi_idle(idle)
+            .with_inverted_output(inverted);
+        let sk6812w = Encoder::<Sk6812w>::new()
+            .with_mosi_idle(idle)
+            .with_inverted_output(inverted);
+        let reset = std::vec![encoder.reset_level(); encoder.reset_size()];
+        let colors = colors();
+
+        let mut out = Vec::new();
+        if encoder.pre_reset() {
+            out.extend_from_slice(&reset);
+        }
+        for color in &colors[..2] {
+            encode(sk6812w, *color, &mut out);
+        }
+        for color in &colors[2..5] {
+            encode(encoder, RGB8::new(color.r, color.g, color.b), &mut out);
+        }
+        let brg = encoder.with_color_order(ColorOrder::BRG);
+        encode(
+            brg,
+            RGB8::new(colors[5].r, colors[5].g, colors[5].b),
+            &mut out,
+        );
+        out.extend_from_slice(&reset);
+        out
+    }
+
+    #[test]
+    fn segments() {
+        for idle in [MosiIdle::Low, MosiIdle::High] {
+            for inverted in [false, true] {
+                let expected = expected(idle, inverted);
+                let size = buffer_size_with_idle(&SEGMENTS, &Timing::DEFAULT, idle, inverted);
+                assert_eq!(expected.len(), size);
+
+                let mut sent = Vec::new();
+                Ws2812::new(Spi(&mut sent), &SEGMENTS)
+                    .with_mosi_idle(idle)
+                    .with_inverted_output(inverted)
+                    .write(colors())
+                    .unwrap();
+                assert_eq!(sent, expected);
+
+                let mut sent = Vec::new();
+                let mut data = std::vec![0; size];
+                prerendered_chain::Ws2812::new(Spi(&mut sent), &SEGMENTS, &mut data)
+                    .with_mosi_idle(idle)
+                    .with_inverted_output(inverted)
+                    .write(colors())
+                    .unwrap();
+                assert_eq!(sent, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn too_many_pixels() {
+        let mut colors = colors();
+        colors.push(RGBW::default());
+
+        let mut sent = Vec::new();
+        let mut leds = Ws2812::new(Spi(&mut sent), &SEGMENTS).with_mosi_idle(MosiIdle::Low);
+        assert_eq!(
+            leds.write(colors.iter().copied()),
+            Err(Error::TooManyPixels)
+        );
+        // The leds of the segments have already been streamed
+        assert_eq!(sent, expected(MosiIdle::Low, false));
+
+        let mut sent = Vec::new();
+        let mut data = std::vec![0; buffer_size(&SEGMENTS, &Timing::DEFAULT) * 2];
+        let mut leds = prerendered_chain::Ws2812::new(Spi(&mut sent), &SEGMENTS, &mut data);
+        assert_eq!(leds.write(colors), Err(Error::TooManyPixels));
+        assert!(sent.is_empty());
+    }
+}
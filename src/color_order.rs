@@ -0,0 +1,200 @@
+//! Order in which the color channels are sent
+//!
+//! Most devices expect green first, but there are plenty of strips using a
+//! different order.
+
+/// Order of the color channels
+///
+/// Consists of the order of the red, green & blue channels and the position
+/// of the white channel for rgbw devices, which is ignored for rgb devices.
+/// `GRB` & `GRBW` are therefore the same. Devices with warm & cold white
+/// channels send both of them at the position of the white channel, warm
+/// white first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorOrder {
+    /// Index into `[r, g, b]` for each channel that's sent
+    rgb: [u8; 3],
+    white_first: bool,
+}
+
+const R: u8 = 0;
+const G: u8 = 1;
+const B: u8 = 2;
+
+impl ColorOrder {
+    pub const RGB: Self = Self::new([R, G, B], false);
+    pub const RBG: Self = Self::new([R, B, G], false);
+    pub const GRB: Self = Self::new([G, R, B], false);
+    pub const GBR: Self = Self::new([G, B, R], false);
+    pub const BRG: Self = Self::new([B, R, G], false);
+    pub const BGR: Self = Self::new([B, G, R], false);
+
+    pub const RGBW: Self = Self::RGB;
+    pub const RBGW: Self = Self::RBG;
+    pub const GRBW: Self = Self::GRB;
+    pub const GBRW: Self = Self::GBR;
+    pub const BRGW: Self = Self::BRG;
+    pub const BGRW: Self = Self::BGR;
+
+    pub const WRGB: Self = Self::new([R, G, B], true);
+    pub const WRBG: Self = Self::new([R, B, G], true);
+    pub const WGRB: Self = Self::new([G, R, B], true);
+    pub const WGBR: Self = Self::new([G, B, R], true);
+    pub const WBRG: Self = Self::new([B, R, G], true);
+    pub const WBGR: Self = Self::new([B, G, R], true);
+
+    const fn new(rgb: [u8; 3], white_first: bool) -> Self {
+        Self { rgb, white_first }
+    }
+
+    /// Is the white channel sent before the color channels
+    pub const fn white_first(&self) -> bool {
+        self.white_first
+    }
+
+    /// Arrange `[r, g, b]` in sending order
+    pub(crate) fn arrange_rgb<T: Copy>(&self, rgb: [T; 3]) -> [T; 3] {
+        [
+            rgb[self.rgb[0] as usize],
+            rgb[self.rgb[1] as usize],
+            rgb[self.rgb[2] as usize],
+        ]
+    }
+
+    /// Get `[r, g, b]` back from channels in sending order
+    pub(crate) fn restore_rgb<T: Copy>(&self, channels: [T; 3]) -> [T; 3] {
+        let mut rgb = channels;
+        for (channel, index) in channels.iter().zip(self.rgb.iter()) {
+            rgb[*index as usize] = *channel;
+        }
+        rgb
+    }
+
+    /// Arrange `[r, g, b]` & `w` in sending order
+    pub(crate) fn arrange_rgbw<T: Copy>(&self, rgb: [T; 3], w: T) -> [T; 4] {
+        let [c0, c1, c2] = self.arrange_rgb(rgb);
+        if self.white_first {
+            [w, c0, c1, c2]
+        } else {
+            [c0, c1, c2, w]
+        }
+    }
+
+    /// Get `[r, g, b]` & `w` back from channels in sending order
+    pub(crate) fn restore_rgbw<T: Copy>(&self, channels: [T; 4]) -> ([T; 3], T) {
+        let [c0, c1, c2, c3] = channels;
+        if self.white_first {
+            (self.restore_rgb([c1, c2, c3]), c0)
+        } else {
+            (self.restore_rgb([c0, c1, c2]), c3)
+        }
+    }
+
+    /// Arrange `[r, g, b]` & `[warm, cold]` in sending order
+    pub(crate) fn arrange_rgbcct<T: Copy>(&self, rgb: [T; 3], white: [T; 2]) -> [T; 5] {
+        let [c0, c1, c2] = self.arrange_rgb(rgb);
+        let [warm, cold] = white;
+        if self.white_first {
+            [warm, cold, c0, c1, c2]
+        } else {
+            [c0, c1, c2, warm, cold]
+        }
+    }
+
+    /// Get `[r, g, b]` & `[warm, cold]` back from channels in sending order
+    pub(crate) fn restore_rgbcct<T: Copy>(&self, channels: [T; 5]) -> ([T; 3], [T; 2]) {
+        let [c0, c1, c2, c3, c4] = channels;
+        if self.white_first {
+            (self.restore_rgb([c2, c3, c4]), [c0, c1])
+        } else {
+            (self.restore_rgb([c0, c1, c2]), [c3, c4])
+        }
+    }
+}
+
+impl Default for ColorOrder {
+    fn default() -> Self {
+        Self::GRB
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [ColorOrder; 18] = [
+        ColorOrder::RGB,
+        ColorOrder::RBG,
+        ColorOrder::GRB,
+        ColorOrder::GBR,
+        ColorOrder::BRG,
+        ColorOrder::BGR,
+        ColorOrder::RGBW,
+        ColorOrder::RBGW,
+        ColorOrder::GRBW,
+        ColorOrder::GBRW,
+        ColorOrder::BRGW,
+        ColorOrder::BGRW,
+        ColorOrder::WRGB,
+        ColorOrder::WRBG,
+        ColorOrder::WGRB,
+        ColorOrder::WGBR,
+        ColorOrder::WBRG,
+        ColorOrder::WBGR,
+    ];
+
+    #[test]
+    fn roundtrip() {
+        let rgb = [1, 2, 3];
+        for order in ALL {
+            assert_eq!(order.restore_rgb(order.arrange_rgb(rgb)), rgb);
+            assert_eq!(order.restore_rgbw(order.arrange_rgbw(rgb, 4)), (rgb, 4));
+            assert_eq!(
+                order.restore_rgbcct(order.arrange_rgbcct(rgb, [4, 5])),
+                (rgb, [4, 5])
+            );
+        }
+    }
+
+    #[test]
+    fn layouts() {
+        let rgb = ['r', 'g', 'b'];
+        assert_eq!(ColorOrder::GRB.arrange_rgb(rgb), ['g', 'r', 'b']);
+        assert_eq!(ColorOrder::BRG.arrange_rgb(rgb), ['b', 'r', 'g']);
+        assert_eq!(ColorOrder::GBR.arrange_rgb(rgb), ['g', 'b', 'r']);
+        assert_eq!(
+            ColorOrder::GRBW.arrange_rgbw(rgb, 'w'),
+            ['g', 'r', 'b', 'w']
+        );
+        assert_eq!(
+            ColorOrder::WGRB.arrange_rgbw(rgb, 'w'),
+            ['w', 'g', 'r', 'b']
+        );
+        assert_eq!(
+            ColorOrder::WBGR.arrange_rgbw(rgb, 'w'),
+            ['w', 'b', 'g', 'r']
+        );
+        // Warm white before cold white
+        assert_eq!(
+            ColorOrder::RGB.arrange_rgbcct(rgb, ['w', 'c']),
+            ['r', 'g', 'b', 'w', 'c']
+        );
+        assert_eq!(
+            ColorOrder::WBRG.arrange_rgbcct(rgb, ['w', 'c']),
+            ['w', 'c', 'b', 'r', 'g']
+        );
+    }
+
+    #[test]
+    fn white_position() {
+        for order in ALL[..12].iter() {
+            assert!(!order.white_first());
+        }
+        for order in ALL[12..].iter() {
+            assert!(order.white_first());
+        }
+        // The rgbw orders are the rgb ones, the white channel is ignored
+        assert_eq!(ColorOrder::GRBW, ColorOrder::GRB);
+        assert_ne!(ColorOrder::WGRB, ColorOrder::GRB);
+    }
+}
@@ -0,0 +1,361 @@
+//! Supported led devices
+//!
+//! Each device carries its timing requirements & the layout of the data for a
+//! single led.
+
+use smart_leds_trait::{CctWhite, White, RGB16, RGB8, RGBCCT, RGBW};
+
+use crate::color_order::ColorOrder;
+use crate::timing::ChipTiming;
+
+/// Maximum amount of bytes sent per led, for all devices (16 bit rgbw)
+pub(crate) const MAX_BYTES_PER_LED: usize = 8;
+
+/// Maximum amount of bytes sent at the start of each frame, for all devices
+pub(crate) const MAX_HEADER_BYTES: usize = 8;
+
+/// A led device
+///
+/// Devices can send at most 8 bytes per led & 8 header bytes, other devices
+/// fail to compile when used by a driver.
+pub trait Device {
+    /// Color of a single led
+    type Color;
+
+    /// Device specific settings, which are sent at the start of each frame
+    type Config: Copy + Default;
+
+    /// Timing requirements
+    const TIMING: ChipTiming;
+
+    /// Order of the color channels used by default
+    const ORDER: ColorOrder;
+
+    /// Amount of bytes sent per led, at most 8
+    const BYTES_PER_LED: usize;
+
+    /// Amount of bytes sent at the start of each frame, at most 8
+    const HEADER_BYTES: usize = 0;
+
+    /// Whether the signal is inverted, so the data line idles high
+    const INVERTED: bool = false;
+
+    /// Write the bytes for a single led into `out`, which is exactly
+    /// [`Device::BYTES_PER_LED`] long
+    fn write_led(color: Self::Color, order: ColorOrder, out: &mut [u8]);
+
+    /// Get the color of a single led back from `data`, which was written by
+    /// [`Device::write_led`]
+    fn read_led(data: &[u8], order: ColorOrder) -> Self::Color;
+
+    /// Write the bytes sent at the start of each frame into `out`, which is
+    /// exactly [`Device::HEADER_BYTES`] long
+    fn write_header(_config: &Self::Config, _order: ColorOrder, _out: &mut [u8]) {}
+}
+
+/// Write rgb leds
+fn write_rgb(color: RGB8, order: ColorOrder, out: &mut [u8]) {
+    out.copy_from_slice(&order.arrange_rgb([color.r, color.g, color.b]));
+}
+
+/// Write rgbw leds
+fn write_rgbw(color: RGBW<u8, u8>, order: ColorOrder, out: &mut [u8]) {
+    out.copy_from_slice(&order.arrange_rgbw([color.r, color.g, color.b], color.a.0));
+}
+
+/// Write rgb leds with warm & cold white channels
+fn write_rgbcct(color: RGBCCT<u8>, order: ColorOrder, out: &mut [u8]) {
+    let CctWhite { warm, cold } = color.a;
+    out.copy_from_slice(&order.arrange_rgbcct([color.r, color.g, color.b], [warm, cold]));
+}
+
+/// Write 16 bit rgb leds, MSB first
+fn write_rgb16(color: RGB16, order: ColorOrder, out: &mut [u8]) {
+    for (channel, out) in order
+        .arrange_rgb([color.r, color.g, color.b])
+        .iter()
+        .zip(out.chunks_exact_mut(2))
+    {
+        out.copy_from_slice(&channel.to_be_bytes());
+    }
+}
+
+/// Write 16 bit rgbw leds, MSB first
+fn write_rgbw16(color: RGBW<u16, u16>, order: ColorOrder, out: &mut [u8]) {
+    for (channel, out) in order
+        .arrange_rgbw([color.r, color.g, color.b], color.a.0)
+        .iter()
+        .zip(out.chunks_exact_mut(2))
+    {
+        out.copy_from_slice(&channel.to_be_bytes());
+    }
+}
+
+/// Read rgb leds
+fn read_rgb(data: &[u8], order: ColorOrder) -> RGB8 {
+    let [r, g, b] = order.restore_rgb([data[0], data[1], data[2]]);
+    RGB8::new(r, g, b)
+}
+
+/// Read rgbw leds
+fn read_rgbw(data: &[u8], order: ColorOrder) -> RGBW<u8, u8> {
+    let ([r, g, b], w) = order.restore_rgbw([data[0], data[1], data[2], data[3]]);
+    RGBW::new_alpha(r, g, b, White(w))
+}
+
+/// Read rgb leds with warm & cold white channels
+fn read_rgbcct(data: &[u8], order: ColorOrder) -> RGBCCT<u8> {
+    let ([r, g, b], [warm, cold]) =
+        order.restore_rgbcct([data[0], data[1], data[2], data[3], data[4]]);
+    RGBCCT::new_alpha(r, g, b, CctWhite { warm, cold })
+}
+
+/// Read 16 bit rgb leds, MSB first
+fn read_rgb16(data: &[u8], order: ColorOrder) -> RGB16 {
+    let channel = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+    let [r, g, b] = order.restore_rgb([channel(0), channel(1), channel(2)]);
+    RGB16::new(r, g, b)
+}
+
+/// Read 16 bit rgbw leds, MSB first
+fn read_rgbw16(data: &[u8], order: ColorOrder) -> RGBW<u16, u16> {
+    let channel = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+    let ([r, g, b], w) = order.restore_rgbw([channel(0), channel(1), channel(2), channel(3)]);
+    RGBW::new_alpha(r, g, b, White(w))
+}
+
+macro_rules! rgb_device {
+    ($(#[$attr:meta])* $name:ident, $timing:expr, $order:expr) => {
+        $(#[$attr])*
+        pub struct $name;
+
+        impl Device for $name {
+            type Color = RGB8;
+            type Config = ();
+            const TIMING: ChipTiming = $timing;
+            const ORDER: ColorOrder = $order;
+            const BYTES_PER_LED: usize = 3;
+
+            fn write_led(color: RGB8, order: ColorOrder, out: &mut [u8]) {
+                write_rgb(color, order, out)
+            }
+
+            fn read_led(data: &[u8], order: ColorOrder) -> RGB8 {
+                read_rgb(data, order)
+            }
+        }
+    };
+}
+
+rgb_device!(Ws2812, ChipTiming::WS2812, ColorOrder::GRB);
+rgb_device!(
+    /// Ws2811 in high speed (800 kHz) mode
+    Ws2811,
+    ChipTiming::WS2811,
+    ColorOrder::RGB
+);
+rgb_device!(
+    /// Ws2811 in low speed (400 kHz) mode
+    Ws2811Slow,
+    ChipTiming::WS2811_SLOW,
+    ColorOrder::RGB
+);
+rgb_device!(Ws2813, ChipTiming::WS2813, ColorOrder::GRB);
+rgb_device!(Ws2815, ChipTiming::WS2815, ColorOrder::GRB);
+rgb_device!(
+    /// Sk6812 without a white channel
+    Sk6812,
+    ChipTiming::SK6812,
+    ColorOrder::GRB
+);
+rgb_device!(Apa106, ChipTiming::APA106, ColorOrder::RGB);
+
+pub struct Sk6812w;
+
+impl Device for Sk6812w {
+    type Color = RGBW<u8, u8>;
+    type Config = ();
+    const TIMING: ChipTiming = ChipTiming::SK6812;
+    const ORDER: ColorOrder = ColorOrder::GRBW;
+    const BYTES_PER_LED: usize = 4;
+
+    fn write_led(color: RGBW<u8, u8>, order: ColorOrder, out: &mut [u8]) {
+        write_rgbw(color, order, out)
+    }
+
+    fn read_led(data: &[u8], order: ColorOrder) -> RGBW<u8, u8> {
+        read_rgbw(data, order)
+    }
+}
+
+/// Ws2805 with warm & cold white channels
+pub struct Ws2805;
+
+impl Device for Ws2805 {
+    type Color = RGBCCT<u8>;
+    type Config = ();
+    const TIMING: ChipTiming = ChipTiming::WS2805;
+    const ORDER: ColorOrder = ColorOrder::RGB;
+    const BYTES_PER_LED: usize = 5;
+
+    fn write_led(color: RGBCCT<u8>, order: ColorOrder, out: &mut [u8]) {
+        write_rgbcct(color, order, out)
+    }
+
+    fn read_led(data: &[u8], order: ColorOrder) -> RGBCCT<u8> {
+        read_rgbcct(data, order)
+    }
+}
+
+/// Ws2816 with 16 bits per channel
+pub struct Ws2816;
+
+impl Device for Ws2816 {
+    type Color = RGB16;
+    type Config = ();
+    const TIMING: ChipTiming = ChipTiming::WS2816;
+    const ORDER: ColorOrder = ColorOrder::GRB;
+    const BYTES_PER_LED: usize = 6;
+
+    fn write_led(color: RGB16, order: ColorOrder, out: &mut [u8]) {
+        write_rgb16(color, order, out)
+    }
+
+    fn read_led(data: &[u8], order: ColorOrder) -> RGB16 {
+        read_rgb16(data, order)
+    }
+}
+
+/// Ucs8904 with 16 bits per channel
+pub struct Ucs8904;
+
+impl Device for Ucs8904 {
+    type Color = RGBW<u16, u16>;
+    type Config = ();
+    const TIMING: ChipTiming = ChipTiming::UCS8904;
+    const ORDER: ColorOrder = ColorOrder::RGBW;
+    const BYTES_PER_LED: usize = 8;
+
+    fn write_led(color: RGBW<u16, u16>, order: ColorOrder, out: &mut [u8]) {
+        write_rgbw16(color, order, out)
+    }
+
+    fn read_led(data: &[u8], order: ColorOrder) -> RGBW<u16, u16> {
+        read_rgbw16(data, order)
+    }
+}
+
+/// Drive current of the tm1814 channels
+///
+/// Each value ranges from 0 (6.5 mA) to 63 (38 mA) in 0.5 mA steps, higher
+/// values are clamped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tm1814Current {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+}
+
+impl Tm1814Current {
+    pub const MIN: Self = Self::new(0, 0, 0, 0);
+    pub const MAX: Self = Self::new(63, 63, 63, 63);
+
+    pub const fn new(r: u8, g: u8, b: u8, w: u8) -> Self {
+        Self { r, g, b, w }
+    }
+}
+
+impl Default for Tm1814Current {
+    fn default() -> Self {
+        Self::MAX
+    }
+}
+
+/// Tm1814 rgbw device
+///
+/// Every frame starts with the drive current of each channel, which can be set
+/// with the `with_config` & `set_config` methods of the drivers.
+///
+/// The signal is inverted, so the data line needs to idle high. If your spi
+/// keeps MOSI low while idle, a reset is sent before each frame.
+pub struct Tm1814;
+
+impl Device for Tm1814 {
+    type Color = RGBW<u8, u8>;
+    type Config = Tm1814Current;
+    const TIMING: ChipTiming = ChipTiming::TM1814;
+    const ORDER: ColorOrder = ColorOrder::WRGB;
+    const BYTES_PER_LED: usize = 4;
+    // The current setting is sent twice, the second time inverted
+    const HEADER_BYTES: usize = 8;
+    const INVERTED: bool = true;
+
+    fn write_led(color: RGBW<u8, u8>, order: ColorOrder, out: &mut [u8]) {
+        write_rgbw(color, order, out)
+    }
+
+    fn read_led(data: &[u8], order: ColorOrder) -> RGBW<u8, u8> {
+        read_rgbw(data, order)
+    }
+
+    fn write_header(config: &Tm1814Current, order: ColorOrder, out: &mut [u8]) {
+        let current = order.arrange_rgbw(
+            [config.r.min(63), config.g.min(63), config.b.min(63)],
+            config.w.min(63),
+        );
+        for (i, channel) in current.iter().enumerate() {
+            out[i] = *channel;
+            out[i + 4] = !*channel;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `color` with the default order of `D` & read it back
+    fn roundtrip<D: Device, const N: usize>(color: D::Color) -> ([u8; N], D::Color) {
+        assert_eq!(D::BYTES_PER_LED, N);
+        let mut data = [0; N];
+        D::write_led(color, D::ORDER, &mut data);
+        (data, D::read_led(&data, D::ORDER))
+    }
+
+    #[test]
+    fn ws2805() {
+        let color = RGBCCT::new_alpha(1, 2, 3, CctWhite { warm: 4, cold: 5 });
+        // Warm white before cold white
+        assert_eq!(roundtrip::<Ws2805, 5>(color), ([1, 2, 3, 4, 5], color));
+
+        let mut data = [0; 5];
+        Ws2805::write_led(color, ColorOrder::WGRB, &mut data);
+        assert_eq!(data, [4, 5, 2, 1, 3]);
+        assert_eq!(Ws2805::read_led(&data, ColorOrder::WGRB), color);
+    }
+
+    #[test]
+    fn ws2816() {
+        let color = RGB16::new(0x0102, 0x0304, 0x0506);
+        // Green first, MSB first
+        assert_eq!(
+            roundtrip::<Ws2816, 6>(color),
+            ([0x03, 0x04, 0x01, 0x02, 0x05, 0x06], color)
+        );
+    }
+
+    #[test]
+    fn ucs8904() {
+        let color = RGBW::new_alpha(0x0102, 0x0304, 0x0506, White(0x0708));
+        assert_eq!(
+            roundtrip::<Ucs8904, 8>(color),
+            ([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08], color)
+        );
+
+        let mut data = [0; 8];
+        Ucs8904::write_led(color, ColorOrder::WBGR, &mut data);
+        assert_eq!(data, [0x07, 0x08, 0x05, 0x06, 0x03, 0x04, 0x01, 0x02]);
+        assert_eq!(Ucs8904::read_led(&data, ColorOrder::WBGR), color);
+    }
+}
@@ -0,0 +1,657 @@
+//! Encoding of led colors into spi data
+//!
+//! The [`Encoder`] turns the colors of a device into the bit patterns of the
+//! [`Timing`], including the header & the reset. All drivers are built on
+//! it, but it can be used for other transports as well, e.g. I2S or a USB
+//! bridge:
+//!
+//! `for byte in Encoder::<devices::Ws2812>::new().encode(colors) { ... }`
+//!
+//! For spis with 16 or 32 bit frames & I2S peripherals, the data can be
+//! encoded into larger [`Word`]s as well.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use crate::color_order::ColorOrder;
+use crate::devices::{self, Device};
+use crate::spi::MosiIdle;
+use crate::timing::Timing;
+use crate::Error;
+
+/// Maximum amount of spi bytes per led (8 spi bits per led bit)
+pub(crate) const MAX_LED_SIZE: usize = devices::MAX_BYTES_PER_LED * 8;
+
+/// Maximum amount of spi bytes for the header (8 spi bits per led bit)
+pub(crate) const MAX_HEADER_SIZE: usize = devices::MAX_HEADER_BYTES * 8;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// Word size of the spi data, implemented for `u8`, `u16` & `u32`
+///
+/// The first byte ends up in the most significant bits, as spis & I2S send
+/// the MSB first.
+pub trait Word: sealed::Sealed + Copy + Default + 'static {
+    /// Amount of bytes per word
+    const BYTES: usize;
+
+    /// Write `data` into `words`, starting at byte `index`
+    ///
+    /// Panics, if `words` is too short.
+    fn write_bytes(words: &mut [Self], index: usize, data: &[u8]);
+}
+
+impl Word for u8 {
+    const BYTES: usize = 1;
+
+    fn write_bytes(words: &mut [u8], index: usize, data: &[u8]) {
+        words[index..index + data.len()].copy_from_slice(data);
+    }
+}
+
+macro_rules! word {
+    ($ty:ty) => {
+        impl Word for $ty {
+            const BYTES: usize = core::mem::size_of::<$ty>();
+
+            fn write_bytes(words: &mut [$ty], index: usize, data: &[u8]) {
+                for (i, byte) in data.iter().enumerate() {
+                    let index = index + i;
+                    let shift = 8 * (Self::BYTES - 1 - index % Self::BYTES);
+                    let word = &mut words[index / Self::BYTES];
+                    *word = (*word & !(0xff << shift)) | (*byte as $ty) << shift;
+                }
+            }
+        }
+    };
+}
+
+word!(u16);
+word!(u32);
+
+/// Settings to encode the colors of device `DEVICE`
+pub struct Encoder<DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    timing: Timing,
+    idle: MosiIdle,
+    inverted: bool,
+    order: ColorOrder,
+    config: DEVICE::Config,
+    device: PhantomData<DEVICE>,
+}
+
+// Derived impls would require `DEVICE: Clone`
+impl<D: Device> Clone for Encoder<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D: Device> Copy for Encoder<D> {}
+
+impl<D: Device> Default for Encoder<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Device> Encoder<D> {
+    // Evaluated when referenced in the constructor, so devices exceeding the
+    // sizes of the internal buffers fail to compile
+    pub(crate) const DEVICE_CHECK: () = assert!(
+        D::BYTES_PER_LED <= devices::MAX_BYTES_PER_LED
+            && D::HEADER_BYTES <= devices::MAX_HEADER_BYTES,
+        "Devices can send at most 8 bytes per led & 8 header bytes"
+    );
+
+    /// Use [`Timing::DEFAULT`] & the color order of the device
+    pub fn new() -> Self {
+        Self::with_device_timing(Timing::DEFAULT)
+    }
+
+    /// Generate the timing of the device for an spi running at `spi_freq` Hz
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn for_freq(spi_freq: u32) -> Option<Self> {
+        Some(Self::with_device_timing(Timing::for_device::<D>(spi_freq)?))
+    }
+
+    fn with_device_timing(timing: Timing) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::DEVICE_CHECK;
+        Self {
+            timing,
+            idle: MosiIdle::DEFAULT,
+            inverted: false,
+            order: D::ORDER,
+            config: Default::default(),
+            device: PhantomData {},
+        }
+    }
+
+    /// Use a different spi bit pattern
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Use a different MOSI idle level
+    pub fn with_mosi_idle(mut self, idle: MosiIdle) -> Self {
+        self.idle = idle;
+        self
+    }
+
+    /// Invert the output, e.g. for inverting level shifters
+    pub fn with_inverted_output(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// Send the color channels in a different order
+    pub fn with_color_order(mut self, order: ColorOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Use different device specific settings
+    pub fn with_config(mut self, config: D::Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Change the device specific settings
+    pub fn set_config(&mut self, config: D::Config) {
+        self.config = config;
+    }
+
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    pub fn order(&self) -> ColorOrder {
+        self.order
+    }
+
+    pub fn config(&self) -> &D::Config {
+        &self.config
+    }
+
+    /// Whether the spi data is inverted, for the device & output
+    pub fn inverted(&self) -> bool {
+        D::INVERTED != self.inverted
+    }
+
+    /// Whether a reset is sent before each frame as well
+    pub fn pre_reset(&self) -> bool {
+        self.idle.pre_reset(self.inverted())
+    }
+
+    /// The spi byte sent during the reset
+    pub fn reset_level(&self) -> u8 {
+        if self.inverted() {
+            0xff
+        } else {
+            0
+        }
+    }
+
+    /// Amount of spi bytes of a single reset
+    pub fn reset_size(&self) -> usize {
+        self.timing.reset_bytes()
+    }
+
+    /// Amount of spi bytes of the header
+    pub fn header_size(&self) -> usize {
+        D::HEADER_BYTES * self.timing.bits() as usize
+    }
+
+    /// Amount of spi bytes per led
+    pub fn led_size(&self) -> usize {
+        D::BYTES_PER_LED * self.timing.bits() as usize
+    }
+
+    /// Amount of spi bytes of a whole frame with `leds` leds, including the
+    /// header & resets
+    pub fn frame_size(&self, leds: usize) -> usize {
+        crate::buffer_size_with_idle::<D>(leds, &self.timing, self.idle, self.inverted)
+    }
+
+    /// Amount of `W` words of a whole frame with `leds` leds
+    ///
+    /// The frame is padded with reset bytes to whole words.
+    pub fn frame_words<W: Word>(&self, leds: usize) -> usize {
+        self.frame_size(leds).div_ceil(W::BYTES)
+    }
+
+    /// Encode a single byte of led data into `out`, which is exactly
+    /// [`Timing::bits`] long
+    pub fn encode_byte(&self, data: u8, out: &mut [u8]) {
+        // Every bit is sent as a symbol of multiple spi bits. High time first,
+        // then the low time
+        self.timing.encode(data, out);
+        if self.inverted() {
+            out.iter_mut().for_each(|b| *b = !*b);
+        }
+    }
+
+    /// Decode a single byte of led data, written by [`Encoder::encode_byte`]
+    pub fn decode_byte(&self, symbols: &[u8]) -> u8 {
+        let value = self.timing.decode(symbols);
+        if self.inverted() {
+            !value
+        } else {
+            value
+        }
+    }
+
+    /// Encode the device specific header into `out`, which is exactly
+    /// [`Encoder::header_size`] long
+    pub fn encode_header(&self, out: &mut [u8]) {
+        let mut header = [0; devices::MAX_HEADER_BYTES];
+        let header = &mut header[..D::HEADER_BYTES];
+        D::write_header(&self.config, self.order, header);
+        self.encode_bytes(header, out);
+    }
+
+    /// Encode a single led into `out`, which is exactly
+    /// [`Encoder::led_size`] long
+    pub fn encode_led(&self, color: D::Color, out: &mut [u8]) {
+        let mut data = [0; devices::MAX_BYTES_PER_LED];
+        let data = &mut data[..D::BYTES_PER_LED];
+        D::write_led(color, self.order, data);
+        self.encode_bytes(data, out);
+    }
+
+    /// Decode a single led, written by [`Encoder::encode_led`]
+    pub fn decode_led(&self, symbols: &[u8]) -> D::Color {
+        let mut data = [0; devices::MAX_BYTES_PER_LED];
+        let data = &mut data[..D::BYTES_PER_LED];
+        let bits = self.timing.bits() as usize;
+        for (byte, symbols) in data.iter_mut().zip(symbols.chunks_exact(bits)) {
+            *byte = self.decode_byte(symbols);
+        }
+        D::read_led(data, self.order)
+    }
+
+    fn encode_bytes(&self, data: &[u8], out: &mut [u8]) {
+        let bits = self.timing.bits() as usize;
+        for (byte, out) in data.iter().zip(out.chunks_exact_mut(bits)) {
+            self.encode_byte(*byte, out);
+        }
+    }
+
+    /// Iterate over the spi bytes of a whole frame with the leds of `iterator`
+    pub fn encode<T, I>(&self, iterator: T) -> Frame<D, T::IntoIter>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        Frame {
+            encoder: *self,
+            leds: iterator.into_iter(),
+            state: State::PreReset,
+            resets: 0,
+            chunk: [0; MAX_LED_SIZE],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Iterate over the spi words of a whole frame with the leds of
+    /// `iterator`, padded with reset bytes to whole words
+    pub fn encode_words<W, T, I>(&self, iterator: T) -> Words<D, T::IntoIter, W>
+    where
+        W: Word,
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        Words {
+            frame: self.encode(iterator),
+            word: PhantomData {},
+        }
+    }
+
+    /// Render a whole frame with the leds of `iterator` into `out`
+    ///
+    /// Returns the amount of rendered bytes, or [`Error::BufferTooSmall`] if
+    /// the frame doesn't fit into `out`.
+    pub fn render<T, I>(&self, iterator: T, out: &mut [u8]) -> Result<usize, Error<Infallible>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        self.render_words(iterator, out)
+    }
+
+    /// Render a whole frame with the leds of `iterator` into the words of
+    /// `out`, padded with reset bytes to whole words
+    ///
+    /// Returns the amount of rendered words, or [`Error::BufferTooSmall`]
+    /// (counting words) if the frame doesn't fit into `out`.
+    pub fn render_words<W, T, I>(
+        &self,
+        iterator: T,
+        out: &mut [W],
+    ) -> Result<usize, Error<Infallible>>
+    where
+        W: Word,
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        let mut out = Render { out, index: 0 };
+        let mut chunk = [0; MAX_LED_SIZE];
+        if self.pre_reset() {
+            self.render_reset(&mut out, self.reset_size());
+        }
+        let header = &mut chunk[..self.header_size()];
+        self.encode_header(header);
+        out.write(header);
+
+        let data = &mut chunk[..self.led_size()];
+        for item in iterator {
+            self.encode_led(item.into(), data);
+            out.write(data);
+        }
+        // Pad the reset to whole words
+        let end = (out.index + self.reset_size()).next_multiple_of(W::BYTES);
+        let amount = end - out.index;
+        self.render_reset(&mut out, amount);
+
+        let needed = out.index / W::BYTES;
+        if needed > out.out.len() {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: out.out.len(),
+            });
+        }
+        Ok(needed)
+    }
+
+    fn render_reset<W: Word>(&self, out: &mut Render<'_, W>, mut amount: usize) {
+        let reset = [self.reset_level(); MAX_LED_SIZE];
+        while amount > 0 {
+            let len = amount.min(reset.len());
+            out.write(&reset[..len]);
+            amount -= len;
+        }
+    }
+}
+
+/// Bytes rendered into words, see [`Encoder::render_words`]
+struct Render<'a, W> {
+    out: &'a mut [W],
+    /// Amount of bytes written
+    index: usize,
+}
+
+impl<'a, W: Word> Render<'a, W> {
+    fn write(&mut self, data: &[u8]) {
+        if self.index + data.len() <= self.out.len() * W::BYTES {
+            W::write_bytes(self.out, self.index, data);
+        }
+        // Keep counting if the buffer is too small, to report the needed size
+        self.index += data.len();
+    }
+}
+
+enum State {
+    PreReset,
+    Header,
+    Leds,
+    Reset,
+    Done,
+}
+
+/// The spi bytes of a whole frame, see [`Encoder::encode`]
+pub struct Frame<D, I>
+where
+    D: Device,
+{
+    encoder: Encoder<D>,
+    leds: I,
+    state: State,
+    /// Remaining reset bytes
+    resets: usize,
+    /// Spi bytes of the current led or header
+    chunk: [u8; MAX_LED_SIZE],
+    len: usize,
+    pos: usize,
+}
+
+impl<D, I, C> Iterator for Frame<D, I>
+where
+    D: Device,
+    I: Iterator<Item = C>,
+    C: Into<D::Color>,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            if self.resets > 0 {
+                self.resets -= 1;
+                return Some(self.encoder.reset_level());
+            }
+            if self.pos < self.len {
+                self.pos += 1;
+                return Some(self.chunk[self.pos - 1]);
+            }
+            self.pos = 0;
+            self.len = 0;
+            match self.state {
+                State::PreReset => {
+                    if self.encoder.pre_reset() {
+                        self.resets = self.encoder.reset_size();
+                    }
+                    self.state = State::Header;
+                }
+                State::Header => {
+                    self.len = self.encoder.header_size();
+                    self.encoder.encode_header(&mut self.chunk[..self.len]);
+                    self.state = State::Leds;
+                }
+                State::Leds => match self.leds.next() {
+                    Some(color) => {
+                        self.len = self.encoder.led_size();
+                        self.encoder
+                            .encode_led(color.into(), &mut self.chunk[..self.len]);
+                    }
+                    None => self.state = State::Reset,
+                },
+                State::Reset => {
+                    self.resets = self.encoder.reset_size();
+                    self.state = State::Done;
+                }
+                State::Done => return None,
+            }
+        }
+    }
+}
+
+/// The spi words of a whole frame, see [`Encoder::encode_words`]
+pub struct Words<D, I, W>
+where
+    D: Device,
+{
+    frame: Frame<D, I>,
+    word: PhantomData<W>,
+}
+
+impl<D, I, C, W> Iterator for Words<D, I, W>
+where
+    D: Device,
+    I: Iterator<Item = C>,
+    C: Into<D::Color>,
+    W: Word,
+{
+    type Item = W;
+
+    fn next(&mut self) -> Option<W> {
+        // Words are at most 4 bytes, `Word` is sealed
+        let mut bytes = [0; 4];
+        let bytes = &mut bytes[..W::BYTES];
+        bytes[0] = self.frame.next()?;
+        for byte in bytes[1..].iter_mut() {
+            // Pad the last word with the reset level
+            *byte = self
+                .frame
+                .next()
+                .unwrap_or_else(|| self.frame.encoder.reset_level());
+        }
+        let mut word = [W::default()];
+        W::write_bytes(&mut word, 0, bytes);
+        Some(word[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use smart_leds_trait::{RGB8, RGBW};
+
+    use super::*;
+    use crate::devices::{Tm1814, Tm1814Current, Ws2812};
+
+    /// Check the iterators & renderers against each other & the sizes
+    fn check<D: Device>(encoder: Encoder<D>, leds: &[D::Color])
+    where
+        D::Color: Copy,
+    {
+        let bytes: Vec<u8> = encoder.encode(leds.iter().copied()).collect();
+        assert_eq!(bytes.len(), encoder.frame_size(leds.len()));
+
+        let mut out = [0; 1024];
+        let len = encoder.render(leds.iter().copied(), &mut out).unwrap();
+        assert_eq!(out[..len], bytes[..]);
+
+        check_words::<D, u16>(encoder, leds, &bytes);
+        check_words::<D, u32>(encoder, leds, &bytes);
+    }
+
+    fn check_words<D: Device, W: Word + core::fmt::Debug + PartialEq>(
+        encoder: Encoder<D>,
+        leds: &[D::Color],
+        bytes: &[u8],
+    ) where
+        D::Color: Copy,
+    {
+        // The bytes packed first byte first, the last word padded with the
+        // reset level
+        let mut expected = std::vec![W::default(); encoder.frame_words::<W>(leds.len())];
+        let mut padded = bytes.to_vec();
+        padded.resize(expected.len() * W::BYTES, encoder.reset_level());
+        W::write_bytes(&mut expected, 0, &padded);
+
+        let words: Vec<W> = encoder.encode_words(leds.iter().copied()).collect();
+        assert_eq!(words, expected);
+
+        let mut out = [W::default(); 1024];
+        let len = encoder
+            .render_words(leds.iter().copied(), &mut out)
+            .unwrap();
+        assert_eq!(out[..len], expected[..]);
+    }
+
+    #[test]
+    fn encode_matches_render() {
+        let leds = [RGB8::new(1, 2, 3), RGB8::new(0xff, 0, 0xa5)];
+        for idle in [MosiIdle::Low, MosiIdle::High] {
+            for inverted in [false, true] {
+                let encoder = Encoder::<Ws2812>::new()
+                    .with_mosi_idle(idle)
+                    .with_inverted_output(inverted);
+                check(encoder, &leds);
+                check(encoder, &leds[..0]);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_pads_words() {
+        // 9 spi bytes per led & 5 reset bytes, so the frames aren't made of
+        // whole words
+        let timing = Timing::from_pattern(3, 1, 2).unwrap().with_reset_bytes(5);
+        let encoder = Encoder::<Ws2812>::new()
+            .with_timing(timing)
+            .with_mosi_idle(MosiIdle::Low);
+        assert_eq!(encoder.frame_size(1), 14);
+        assert_eq!(encoder.frame_words::<u16>(1), 7);
+        assert_eq!(encoder.frame_words::<u32>(2), 6);
+        check(encoder, &[RGB8::new(1, 2, 3)]);
+        check(encoder, &[RGB8::new(1, 2, 3); 2]);
+        check(encoder.with_inverted_output(true), &[RGB8::new(1, 2, 3)]);
+    }
+
+    #[test]
+    fn tm1814_frame() {
+        let current = Tm1814Current::new(1, 2, 3, 4);
+        let encoder = Encoder::<Tm1814>::new()
+            .with_mosi_idle(MosiIdle::Low)
+            .with_config(current);
+        // Inverted device, so MOSI idling low needs a reset before the frame
+        assert!(encoder.pre_reset());
+        assert_eq!(encoder.reset_level(), 0xff);
+        let leds = [RGBW {
+            r: 1,
+            g: 2,
+            b: 3,
+            a: smart_leds_trait::White(4),
+        }];
+        check(encoder, &leds);
+
+        let bytes: Vec<u8> = encoder.encode(leds.iter().copied()).collect();
+        let reset = encoder.reset_size();
+        let bits = encoder.timing().bits() as usize;
+        assert!(bytes[..reset].iter().all(|b| *b == 0xff));
+        assert!(bytes[bytes.len() - reset..].iter().all(|b| *b == 0xff));
+        let header: Vec<u8> = bytes[reset..reset + encoder.header_size()]
+            .chunks_exact(bits)
+            .map(|symbols| encoder.decode_byte(symbols))
+            .collect();
+        assert_eq!(header, [4, 1, 2, 3, !4, !1, !2, !3]);
+        let led = &bytes[reset + encoder.header_size()..][..encoder.led_size()];
+        assert_eq!(encoder.decode_led(led), leds[0]);
+    }
+
+    #[test]
+    fn frame_size_matches_buffer_size() {
+        for leds in [0, 1, 60] {
+            assert_eq!(
+                Encoder::<Ws2812>::new().frame_size(leds),
+                crate::buffer_size::<Ws2812>(leds)
+            );
+            assert_eq!(
+                Encoder::<Tm1814>::new().frame_size(leds),
+                crate::buffer_size::<Tm1814>(leds)
+            );
+            assert_eq!(
+                Encoder::<Ws2812>::new().frame_words::<u32>(leds),
+                crate::buffer_words::<Ws2812, u32>(leds)
+            );
+        }
+    }
+
+    #[test]
+    fn render_too_small() {
+        let encoder = Encoder::<Ws2812>::new();
+        let mut out = [0; 20];
+        assert_eq!(
+            encoder.render([RGB8::default(); 2], &mut out),
+            Err(Error::BufferTooSmall {
+                needed: encoder.frame_size(2),
+                available: 20,
+            })
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! Errors of the drivers
+
+use core::convert::Infallible;
+use core::fmt;
+
+/// Error of a driver, wrapping the error `E` of the spi (or uart)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Error of the spi, or the uart of the [`uart`](crate::uart) variant
+    Spi(E),
+    /// The buffer is too small for the rendered frame
+    BufferTooSmall { needed: usize, available: usize },
+    /// The led index is larger than the amount of leds
+    IndexOutOfRange,
+    /// There are more colors than leds
+    TooManyPixels,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Self::Spi(e)
+    }
+}
+
+impl Error<Infallible> {
+    /// Use as error of a driver with spi error `E`
+    pub(crate) fn cast<E>(self) -> Error<E> {
+        match self {
+            Self::Spi(e) => match e {},
+            Self::BufferTooSmall { needed, available } => {
+                Error::BufferTooSmall { needed, available }
+            }
+            Self::IndexOutOfRange => Error::IndexOutOfRange,
+            Self::TooManyPixels => Error::TooManyPixels,
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spi(e) => write!(f, "spi error: {:?}", e),
+            Self::BufferTooSmall { needed, available } => write!(
+                f,
+                "buffer of {} bytes is too small, {} bytes are needed",
+                available, needed
+            ),
+            Self::IndexOutOfRange => f.write_str("led index out of range"),
+            Self::TooManyPixels => f.write_str("more colors than leds"),
+        }
+    }
+}
@@ -3,53 +3,225 @@
 //! - For usage with `smart-leds`
 //! - Implements the `SmartLedsWrite` trait
 //!
-//! Needs a type implementing the `spi::FullDuplex` trait.
+//! Needs a type implementing the embedded-hal 1.0 `spi::SpiBus` trait. Other
+//! spi traits can be used via the wrappers in [`spi`].
 //!
-//! The spi peripheral should run at 2MHz to 3.8 MHz
-
-// Timings for ws2812 from https://cpldcpu.files.wordpress.com/2014/01/ws2812_timing_table.png
-// Timings for sk6812 from https://cpldcpu.wordpress.com/2016/03/09/the-sk6812-another-intelligent-rgb-led/
+//! The spi peripheral should run at 2MHz to 3.8 MHz, other frequencies can be
+//! used by configuring a matching [`timing::Timing`].
+//!
+//! Boards without a free spi can use a uart instead, see [`uart`].
 
-#![allow(incomplete_features)]
-#![feature(generic_const_exprs)]
 #![no_std]
 
 use embedded_hal as hal;
 
+/// The builders forwarding to the [`Encoder`](encoder::Encoder) of a driver,
+/// for impl blocks of device `$device` with an `encoder` field
+macro_rules! encoder_builders {
+    ($device:ident) => {
+        /// Use the settings of an [`Encoder`](crate::encoder::Encoder)
+        pub fn with_encoder(mut self, encoder: crate::encoder::Encoder<$device>) -> Self {
+            self.encoder = encoder;
+            self
+        }
+
+        /// Use a different spi bit pattern
+        ///
+        /// This is needed if the spi doesn't run within the frequency range
+        /// supported by [`Timing::DEFAULT`](crate::timing::Timing::DEFAULT).
+        pub fn with_timing(mut self, timing: crate::timing::Timing) -> Self {
+            self.encoder = self.encoder.with_timing(timing);
+            self
+        }
+
+        /// Use a different MOSI idle level
+        ///
+        /// If MOSI idles high, a reset is sent before each frame as well. By
+        /// default this depends on the `mosi_idle_high` feature.
+        pub fn with_mosi_idle(mut self, idle: crate::spi::MosiIdle) -> Self {
+            self.encoder = self.encoder.with_mosi_idle(idle);
+            self
+        }
+
+        /// Invert the output, e.g. for inverting level shifters
+        ///
+        /// Every spi bit is complemented & the reset is sent as high bytes, so
+        /// MOSI should idle high (see [`MosiIdle`](crate::spi::MosiIdle)).
+        pub fn with_inverted_output(mut self, inverted: bool) -> Self {
+            self.encoder = self.encoder.with_inverted_output(inverted);
+            self
+        }
+
+        /// Send the color channels in a different order
+        ///
+        /// By default, the channels are sent in the order of the device
+        /// ([`Device::ORDER`](crate::devices::Device::ORDER)).
+        pub fn with_color_order(mut self, order: crate::color_order::ColorOrder) -> Self {
+            self.encoder = self.encoder.with_color_order(order);
+            self
+        }
+
+        /// Use different device specific settings
+        ///
+        /// These are sent at the start of every frame, e.g. the drive currents
+        /// of [`Tm1814`](crate::devices::Tm1814).
+        pub fn with_config(mut self, config: <$device as crate::devices::Device>::Config) -> Self {
+            self.encoder = self.encoder.with_config(config);
+            self
+        }
+
+        /// Change the device specific settings
+        pub fn set_config(&mut self, config: <$device as crate::devices::Device>::Config) {
+            self.encoder.set_config(config);
+        }
+    };
+}
+
+pub mod chain;
+pub mod color_order;
+pub mod devices;
+pub mod encoder;
+mod error;
+pub mod parallel;
 pub mod prerendered;
+#[cfg(feature = "async")]
+pub mod prerendered_async;
+pub mod prerendered_chain;
+#[cfg(feature = "dma")]
+pub mod prerendered_dma;
 pub mod prerendered_static;
+pub mod spi;
+pub mod timing;
+pub mod uart;
 
-use hal::spi::{FullDuplex, Mode, Phase, Polarity};
+use hal::spi::{Mode, Phase, Polarity};
 
-use core::marker::PhantomData;
+pub use error::Error;
 
-use smart_leds_trait::{SmartLedsWrite, RGB8, RGBW};
+use smart_leds_trait::SmartLedsWrite;
 
-use nb;
-use nb::block;
+use devices::Device;
+use encoder::{Encoder, Word};
+use spi::MosiIdle;
+use timing::Timing;
 
 /// SPI mode that can be used for this crate
 ///
 /// Provided for convenience
 /// Doesn't really matter
+///
+/// The leds only see MOSI, which should idle low (or high with inverted
+/// output). Otherwise configure the idle level with `with_mosi_idle`.
 pub const MODE: Mode = Mode {
     polarity: Polarity::IdleLow,
     phase: Phase::CaptureOnFirstTransition,
 };
 
-pub mod devices {
-    pub struct Ws2812;
-    pub struct Sk6812w;
+/// Size of the buffer needed by the prerendered variants for `leds` leds of
+/// device `D` with [`Timing::DEFAULT`]
+///
+/// This includes the header & the reset, which is sent twice with the default
+/// [`MosiIdle`] of the `mosi_idle_high` feature. It can be used for array
+/// lengths:
+/// `[0u8; buffer_size::<devices::Sk6812w>(60)]`
+pub const fn buffer_size<D: Device>(leds: usize) -> usize {
+    buffer_size_with_timing::<D>(leds, &Timing::DEFAULT)
+}
+
+/// Size of the buffer needed by the prerendered variants for `leds` leds of
+/// device `D` with `timing`
+pub const fn buffer_size_with_timing<D: Device>(leds: usize, timing: &Timing) -> usize {
+    buffer_size_with_idle::<D>(leds, timing, MosiIdle::DEFAULT, false)
+}
+
+/// Size of the buffer needed by the prerendered variants for `leds` leds of
+/// device `D` with `timing`, for drivers using a different MOSI idle level
+/// (see `with_mosi_idle`) or `inverted` output (see `with_inverted_output`)
+pub const fn buffer_size_with_idle<D: Device>(
+    leds: usize,
+    timing: &Timing,
+    idle: MosiIdle,
+    inverted: bool,
+) -> usize {
+    (D::HEADER_BYTES + leds * D::BYTES_PER_LED) * timing.bits() as usize
+        + idle.resets(D::INVERTED != inverted) * timing.reset_bytes()
+}
+
+/// Size of the buffer needed by the prerendered variants for `leds` leds of
+/// device `D`, using the timing generated for `spi_freq` (see the
+/// `new_device` constructors)
+///
+/// Panics (or fails to compile, if evaluated at compile time), if the device
+/// can't be driven at this frequency.
+pub const fn buffer_size_for_freq<D: Device>(leds: usize, spi_freq: u32) -> usize {
+    match Timing::for_device::<D>(spi_freq) {
+        Some(timing) => buffer_size_with_timing::<D>(leds, &timing),
+        None => panic!("The device can't be driven at this spi frequency"),
+    }
+}
+
+/// Amount of `W` words needed by the prerendered variant for `leds` leds of
+/// device `D`, e.g. `u16` for spis with 16 bit frames
+pub const fn buffer_words<D: Device, W: Word>(leds: usize) -> usize {
+    buffer_size::<D>(leds).div_ceil(W::BYTES)
 }
 
-pub struct Ws2812<SPI, DEVICE = devices::Ws2812> {
+/// Declare a `static mut` buffer for the prerendered variants
+///
+/// Takes the name, the device type, the amount of leds & optionally the
+/// [`Timing`], as well as the [`MosiIdle`] level & inverted output. The size
+/// is calculated with [`buffer_size`], [`buffer_size_with_timing`] or
+/// [`buffer_size_with_idle`]:
+/// `static_buffer!(pub BUFFER, devices::Sk6812w, 60);`
+/// `static_buffer!(BUFFER, devices::Ws2812, 60, Timing::DEFAULT, MosiIdle::High, false);`
+///
+/// For `u16` or `u32` words, the word type follows the name & the size is
+/// calculated with [`buffer_words`]:
+/// `static_buffer!(BUFFER: u16, devices::Ws2812, 60);`
+///
+/// Take the buffer once, through a raw pointer instead of a reference to the
+/// `static mut` (which is denied by the `static_mut_refs` lint):
+/// `let buffer = unsafe { &mut *core::ptr::addr_of_mut!(BUFFER) };`
+#[macro_export]
+macro_rules! static_buffer {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $device:ty, $leds:expr) => {
+        $(#[$attr])*
+        $vis static mut $name: [u8; $crate::buffer_size::<$device>($leds)] =
+            [0; $crate::buffer_size::<$device>($leds)];
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident, $device:ty, $leds:expr, $timing:expr) => {
+        $(#[$attr])*
+        $vis static mut $name: [u8; $crate::buffer_size_with_timing::<$device>($leds, &$timing)] =
+            [0; $crate::buffer_size_with_timing::<$device>($leds, &$timing)];
+    };
+    (
+        $(#[$attr:meta])* $vis:vis $name:ident,
+        $device:ty, $leds:expr, $timing:expr, $idle:expr, $inverted:expr
+    ) => {
+        $(#[$attr])*
+        $vis static mut $name: [
+            u8;
+            $crate::buffer_size_with_idle::<$device>($leds, &$timing, $idle, $inverted)
+        ] = [0; $crate::buffer_size_with_idle::<$device>($leds, &$timing, $idle, $inverted)];
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident: $word:ty, $device:ty, $leds:expr) => {
+        $(#[$attr])*
+        $vis static mut $name: [$word; $crate::buffer_words::<$device, $word>($leds)] =
+            [0; $crate::buffer_words::<$device, $word>($leds)];
+    };
+}
+
+pub struct Ws2812<SPI, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
     spi: SPI,
-    device: PhantomData<DEVICE>,
+    encoder: Encoder<DEVICE>,
 }
 
 impl<SPI, E> Ws2812<SPI>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<Error = E>,
 {
     /// Use ws2812 devices via spi
     ///
@@ -62,14 +234,14 @@ where
     pub fn new(spi: SPI) -> Self {
         Self {
             spi,
-            device: PhantomData {},
+            encoder: Encoder::new(),
         }
     }
 }
 
 impl<SPI, E> Ws2812<SPI, devices::Sk6812w>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<Error = E>,
 {
     /// Use sk6812w devices via spi
     ///
@@ -84,103 +256,126 @@ where
     pub fn new_sk6812w(spi: SPI) -> Self {
         Self {
             spi,
-            device: PhantomData {},
+            encoder: Encoder::new(),
         }
     }
 }
 
 impl<SPI, D, E> Ws2812<SPI, D>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<Error = E>,
+    D: Device,
 {
-    /// Write a single byte for ws2812 devices
-    fn write_byte(&mut self, mut data: u8) -> Result<(), E> {
-        // Send two bits in one spi byte. High time first, then the low time
-        // The maximum for T0H is 500ns, the minimum for one bit 1063 ns.
-        // These result in the upper and lower spi frequency limits
-        let patterns = [0b1000_1000, 0b1000_1110, 0b11101000, 0b11101110];
-        for _ in 0..4 {
-            let bits = (data & 0b1100_0000) >> 6;
-            block!(self.spi.send(patterns[bits as usize]))?;
-            block!(self.spi.read()).ok();
-            data <<= 2;
-        }
-        Ok(())
+    /// Use any supported device via spi
+    ///
+    /// The spi bit pattern & reset time are generated from the timing of the
+    /// device, for an spi running at `spi_freq` Hz.
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn new_device(spi: SPI, spi_freq: u32) -> Option<Self> {
+        Some(Self {
+            spi,
+            encoder: Encoder::for_freq(spi_freq)?,
+        })
+    }
+
+    encoder_builders!(D);
+
+    /// Write the device specific header sent at the start of each frame
+    fn write_header(&mut self) -> Result<(), E> {
+        let mut out = [0; encoder::MAX_HEADER_SIZE];
+        let out = &mut out[..self.encoder.header_size()];
+        self.encoder.encode_header(out);
+        self.spi.write(out)
+    }
+
+    /// Write the data for a single led
+    fn write_led(&mut self, color: D::Color) -> Result<(), E> {
+        let mut out = [0; encoder::MAX_LED_SIZE];
+        let out = &mut out[..self.encoder.led_size()];
+        self.encoder.encode_led(color, out);
+        self.spi.write(out)
     }
 
     fn flush(&mut self) -> Result<(), E> {
-        // Should be > 300μs, so for an SPI Freq. of 3.8MHz, we have to send at least 1140 low bits or 140 low bytes
-        for _ in 0..140 {
-            block!(self.spi.send(0))?;
-            block!(self.spi.read()).ok();
-        }
-        Ok(())
+        spi::write_reset(
+            &mut self.spi,
+            self.encoder.reset_size(),
+            self.encoder.reset_level(),
+        )
     }
 }
 
-impl<SPI, E> SmartLedsWrite for Ws2812<SPI>
+impl<SPI, D, E> SmartLedsWrite for Ws2812<SPI, D>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<Error = E>,
+    D: Device,
 {
-    type Error = E;
-    type Color = RGB8;
+    type Error = Error<E>;
+    type Color = D::Color;
     /// Write all the items of an iterator to a ws2812 strip
-    fn write<T, I>(&mut self, iterator: T) -> Result<(), E>
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
     where
-        T: Iterator<Item = I>,
+        T: IntoIterator<Item = I>,
         I: Into<Self::Color>,
     {
-        // We introduce an offset in the fifo here, so there's always one byte in transit
-        // Some MCUs (like the stm32f1) only a one byte fifo, which would result
-        // in overrun error if two bytes need to be stored
-        block!(self.spi.send(0))?;
-        if cfg!(feature = "mosi_idle_high") {
+        if self.encoder.pre_reset() {
             self.flush()?;
         }
+        self.write_header()?;
 
         for item in iterator {
-            let item = item.into();
-            self.write_byte(item.g)?;
-            self.write_byte(item.r)?;
-            self.write_byte(item.b)?;
+            self.write_led(item.into())?;
         }
         self.flush()?;
-        // Now, resolve the offset we introduced at the beginning
-        block!(self.spi.read())?;
-        Ok(())
+        self.spi.flush().map_err(Error::Spi)
     }
 }
 
-impl<SPI, E> SmartLedsWrite for Ws2812<SPI, devices::Sk6812w>
-where
-    SPI: FullDuplex<u8, Error = E>,
-{
-    type Error = E;
-    type Color = RGBW<u8, u8>;
-    /// Write all the items of an iterator to a ws2812 strip
-    fn write<T, I>(&mut self, iterator: T) -> Result<(), E>
-    where
-        T: Iterator<Item = I>,
-        I: Into<Self::Color>,
-    {
-        // We introduce an offset in the fifo here, so there's always one byte in transit
-        // Some MCUs (like the stm32f1) only a one byte fifo, which would result
-        // in overrun error if two bytes need to be stored
-        block!(self.spi.send(0))?;
-        if cfg!(feature = "mosi_idle_high") {
-            self.flush()?;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        for item in iterator {
-            let item = item.into();
-            self.write_byte(item.g)?;
-            self.write_byte(item.r)?;
-            self.write_byte(item.b)?;
-            self.write_byte(item.a.0)?;
-        }
-        self.flush()?;
-        // Now, resolve the offset we introduced at the beginning
-        block!(self.spi.read())?;
-        Ok(())
+    static_buffer!(BUFFER, devices::Sk6812w, 3);
+    static_buffer!(
+        TIMED,
+        devices::Ws2812,
+        3,
+        Timing::DEFAULT.with_reset_bytes(10)
+    );
+    static_buffer!(
+        IDLE,
+        devices::Ws2812,
+        3,
+        Timing::DEFAULT,
+        MosiIdle::High,
+        false
+    );
+    static_buffer!(
+        INVERTED,
+        devices::Ws2812,
+        3,
+        Timing::DEFAULT,
+        MosiIdle::Low,
+        true
+    );
+    static_buffer!(WORDS: u32, devices::Ws2812, 3);
+
+    #[test]
+    fn static_buffer_sizes() {
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(BUFFER) };
+        assert_eq!(buffer.len(), buffer_size::<devices::Sk6812w>(3));
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(TIMED) };
+        assert_eq!(
+            buffer.len(),
+            3 * 3 * 4 + 10 * MosiIdle::DEFAULT.resets(false)
+        );
+        // Two resets each
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(IDLE) };
+        assert_eq!(buffer.len(), 3 * 3 * 4 + 2 * 140);
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(INVERTED) };
+        assert_eq!(buffer.len(), 3 * 3 * 4 + 2 * 140);
+        let buffer = unsafe { &mut *core::ptr::addr_of_mut!(WORDS) };
+        assert_eq!(buffer.len(), buffer_size::<devices::Ws2812>(3).div_ceil(4));
     }
 }
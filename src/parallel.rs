@@ -0,0 +1,330 @@
+//! Parallel variant, driving 2, 4 or 8 strips at once
+//!
+//! With a quad or octal spi (or a parallel GPIO DMA), multiple data lines are
+//! clocked together. The spi data of each strip is encoded as usual, then the
+//! bits of all strips are interleaved: with `LANES` data lines, every group of
+//! `LANES` bits is sent in a single clock, lane `n` on data line `n` (lane 0 in
+//! the LSB of each group).
+//!
+//! All strips use the same device & settings, so they share the same timing.
+//! Shorter strips are padded with the reset level. The needed buffer size can
+//! be calculated with [`buffer_size`].
+//!
+//! The peripheral is connected by implementing [`Write`].
+
+use core::convert::Infallible;
+
+pub use crate::devices;
+use crate::devices::Device;
+use crate::encoder::Encoder as LaneEncoder;
+use crate::Error;
+
+/// Sink sending the interleaved data of `LANES` data lines in parallel, e.g.
+/// a quad spi for 4 lanes
+pub trait Write<const LANES: usize> {
+    type Error;
+
+    /// Write all bytes of `data`, MSB first
+    ///
+    /// Every group of `LANES` bits is sent in a single clock. This may return
+    /// before the data is completely sent out.
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Wait until all previously written data is sent out
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Size of the buffer needed by the parallel variant for `lanes` strips of
+/// device `D` with up to `leds` leds each
+pub const fn buffer_size<D: Device>(lanes: usize, leds: usize) -> usize {
+    crate::buffer_size::<D>(leds) * lanes
+}
+
+/// Encodes `LANES` strips into interleaved data
+pub struct Encoder<const LANES: usize, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    encoder: LaneEncoder<DEVICE>,
+}
+
+// Derived impls would require `DEVICE: Clone`
+impl<const LANES: usize, D: Device> Clone for Encoder<LANES, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<const LANES: usize, D: Device> Copy for Encoder<LANES, D> {}
+
+impl<const LANES: usize, D: Device> Encoder<LANES, D> {
+    // Evaluated when referenced in the constructor, so other amounts of lanes
+    // fail to compile
+    const LANES_CHECK: () = assert!(
+        LANES == 2 || LANES == 4 || LANES == 8,
+        "Only 2, 4 or 8 lanes are supported"
+    );
+
+    /// Encode each strip with the settings of `encoder`
+    pub fn new(encoder: LaneEncoder<D>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::LANES_CHECK;
+        Self { encoder }
+    }
+
+    /// The settings of each strip
+    pub fn encoder(&self) -> &LaneEncoder<D> {
+        &self.encoder
+    }
+
+    /// Amount of bytes of a whole frame, with up to `leds` leds per strip
+    pub fn frame_size(&self, leds: usize) -> usize {
+        self.encoder.frame_size(leds) * LANES
+    }
+
+    /// Render a whole frame with one iterator of leds per strip into `out`
+    ///
+    /// Returns the amount of rendered bytes, or [`Error::BufferTooSmall`] if
+    /// the frame doesn't fit into `out`.
+    pub fn render<T, I>(
+        &self,
+        strips: [T; LANES],
+        out: &mut [u8],
+    ) -> Result<usize, Error<Infallible>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        let mut frames = strips.map(|strip| self.encoder.encode(strip));
+        let level = self.encoder.reset_level();
+        let mut index = 0;
+        loop {
+            let mut bytes = [level; LANES];
+            let mut done = true;
+            for (byte, frame) in bytes.iter_mut().zip(frames.iter_mut()) {
+                if let Some(data) = frame.next() {
+                    *byte = data;
+                    done = false;
+                }
+            }
+            if done {
+                break;
+            }
+            if let Some(out) = out.get_mut(index..index + LANES) {
+                interleave(&bytes, out);
+            }
+            // Keep counting if the buffer is too small, to report the needed
+            // size
+            index += LANES;
+        }
+
+        if index > out.len() {
+            return Err(Error::BufferTooSmall {
+                needed: index,
+                available: out.len(),
+            });
+        }
+        Ok(index)
+    }
+}
+
+/// Interleave one byte per lane into `out`, which is exactly `LANES` bytes
+/// long
+fn interleave<const LANES: usize>(bytes: &[u8; LANES], out: &mut [u8]) {
+    let mut bits: u64 = 0;
+    for bit in (0..8).rev() {
+        // The highest lane first, so lane 0 ends up in the LSB of the group
+        for byte in bytes.iter().rev() {
+            bits = (bits << 1) | ((*byte >> bit) & 1) as u64;
+        }
+    }
+    for (i, out) in out.iter_mut().rev().enumerate() {
+        *out = (bits >> (8 * i)) as u8;
+    }
+}
+
+/// Driver for `LANES` strips, rendering into a buffer
+pub struct Ws2812<'a, BUS, const LANES: usize, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    bus: BUS,
+    data: &'a mut [u8],
+    encoder: LaneEncoder<DEVICE>,
+}
+
+impl<'a, BUS, E, const LANES: usize> Ws2812<'a, BUS, LANES>
+where
+    BUS: Write<LANES, Error = E>,
+{
+    /// Use ws2812 devices via a parallel bus
+    ///
+    /// Each data line should run within 2 MHz to 3.8 MHz
+    ///
+    /// You need to provide a buffer `data`, whose length is at least
+    /// [`buffer_size`] for the longest strip.
+    pub fn new(bus: BUS, data: &'a mut [u8]) -> Self {
+        Self::with_device_encoder(bus, data, LaneEncoder::new())
+    }
+}
+
+impl<'a, BUS, E, const LANES: usize> Ws2812<'a, BUS, LANES, devices::Sk6812w>
+where
+    BUS: Write<LANES, Error = E>,
+{
+    /// Use sk6812w devices via a parallel bus
+    ///
+    /// Each data line should run within 2.3 MHz to 3.8 MHz at least.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least
+    /// [`buffer_size`] for the longest strip.
+    pub fn new_sk6812w(bus: BUS, data: &'a mut [u8]) -> Self {
+        Self::with_device_encoder(bus, data, LaneEncoder::new())
+    }
+}
+
+impl<'a, BUS, D, E, const LANES: usize> Ws2812<'a, BUS, LANES, D>
+where
+    BUS: Write<LANES, Error = E>,
+    D: Device,
+{
+    /// Use any supported device via a parallel bus
+    ///
+    /// The bit pattern & reset time are generated from the timing of the
+    /// device, for data lines clocked at `freq` Hz.
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn new_device(bus: BUS, data: &'a mut [u8], freq: u32) -> Option<Self> {
+        Some(Self::with_device_encoder(
+            bus,
+            data,
+            LaneEncoder::for_freq(freq)?,
+        ))
+    }
+
+    fn with_device_encoder(bus: BUS, data: &'a mut [u8], encoder: LaneEncoder<D>) -> Self {
+        // Fail to compile for unsupported amounts of lanes, even if `write`
+        // is never used
+        #[allow(clippy::let_unit_value)]
+        let () = Encoder::<LANES, D>::LANES_CHECK;
+        Self { bus, data, encoder }
+    }
+
+    encoder_builders!(D);
+
+    /// Write one iterator of leds per strip & send them
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the frame doesn't fit into the
+    /// buffer.
+    pub fn write<T, I>(&mut self, strips: [T; LANES]) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        let len = Encoder::<LANES, D>::new(self.encoder)
+            .render(strips, self.data)
+            .map_err(Error::cast)?;
+        self.bus.write(&self.data[..len])?;
+        self.bus.flush().map_err(Error::Spi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use smart_leds_trait::RGB8;
+
+    use crate::spi::MosiIdle;
+
+    use super::*;
+
+    /// The bit of `lane` sent in clock `clock` of the interleaved `data`
+    fn bit<const LANES: usize>(data: &[u8], clock: usize, lane: usize) -> u8 {
+        let index = clock * LANES + LANES - 1 - lane;
+        (data[index / 8] >> (7 - index % 8)) & 1
+    }
+
+    /// Get the bytes of every lane back from the interleaved `data`
+    fn deinterleave<const LANES: usize>(data: &[u8]) -> [Vec<u8>; LANES] {
+        core::array::from_fn(|lane| {
+            (0..data.len() / LANES)
+                .map(|byte| {
+                    (0..8).fold(0, |value, i| {
+                        value << 1 | bit::<LANES>(data, byte * 8 + i, lane)
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn check_interleave<const LANES: usize>() {
+        let bytes: [u8; LANES] =
+            core::array::from_fn(|lane| 0xa5u8.rotate_left(lane as u32) ^ lane as u8);
+        let mut out = [0; LANES];
+        interleave(&bytes, &mut out);
+        for (lane, byte) in bytes.iter().enumerate() {
+            for clock in 0..8 {
+                assert_eq!(bit::<LANES>(&out, clock, lane), (byte >> (7 - clock)) & 1);
+            }
+        }
+
+        // Lane 0 in the LSB of each group
+        let mut bytes = [0; LANES];
+        bytes[0] = 0xff;
+        interleave(&bytes, &mut out);
+        let bits = out.iter().fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+        for group in 0..8 {
+            assert_eq!((bits >> (group * LANES)) & ((1 << LANES) - 1), 1);
+        }
+    }
+
+    #[test]
+    fn interleave_lanes() {
+        let mut out = [0; 2];
+        interleave(&[0xff, 0x00], &mut out);
+        assert_eq!(out, [0b0101_0101; 2]);
+        let mut out = [0; 4];
+        interleave(&[0xf0, 0x00, 0x00, 0x0f], &mut out);
+        assert_eq!(out, [0x11, 0x11, 0x88, 0x88]);
+
+        check_interleave::<2>();
+        check_interleave::<4>();
+        check_interleave::<8>();
+    }
+
+    fn check_padding<const LANES: usize>(inverted: bool) {
+        let lane = LaneEncoder::<devices::Ws2812>::new()
+            .with_mosi_idle(MosiIdle::Low)
+            .with_inverted_output(inverted);
+        let encoder = Encoder::<LANES, _>::new(lane);
+        let leds = [RGB8::new(1, 2, 3), RGB8::new(0xa5, 0x5a, 0xff)];
+        // Lane 0 has both leds, all others only the first one
+        let strips =
+            core::array::from_fn(|lane| leds[..if lane == 0 { 2 } else { 1 }].iter().copied());
+
+        let mut out = std::vec![0; encoder.frame_size(2)];
+        let len = encoder.render(strips, &mut out).unwrap();
+        assert_eq!(len, encoder.frame_size(2));
+
+        let lanes = deinterleave::<LANES>(&out[..len]);
+        let long: Vec<u8> = lane.encode(leds.iter().copied()).collect();
+        assert_eq!(lanes[0], long);
+        let mut short: Vec<u8> = lane.encode(leds[..1].iter().copied()).collect();
+        short.resize(long.len(), lane.reset_level());
+        for data in &lanes[1..] {
+            assert_eq!(*data, short);
+        }
+    }
+
+    #[test]
+    fn shorter_strips_are_padded() {
+        for inverted in [false, true] {
+            check_padding::<2>(inverted);
+            check_padding::<4>(inverted);
+            check_padding::<8>(inverted);
+        }
+    }
+}
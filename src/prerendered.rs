@@ -2,42 +2,50 @@
 //!
 //! This approach minimizes timing issues, at the cost of much higher ram usage.
 //! It also increases the needed time.
+//!
+//! The whole frame, including the reset time, is handed to the spi at once, so
+//! HALs using DMA can send it without any gaps.
 
 use embedded_hal as hal;
 
-use hal::spi::{FullDuplex, Mode, Phase, Polarity};
-
-use core::marker::PhantomData;
+use hal::spi::{Mode, Phase, Polarity};
 
-use smart_leds_trait::{SmartLedsWrite, RGB8, RGBW};
+use smart_leds_trait::SmartLedsWrite;
 
-use nb;
-use nb::block;
+use crate::spi;
+use crate::Error;
 
 /// SPI mode that can be used for this crate
 ///
 /// Provided for convenience
 /// Doesn't really matter
+///
+/// The leds only see MOSI, which should idle low (or high with inverted
+/// output). Otherwise configure the idle level with `with_mosi_idle`.
 pub const MODE: Mode = Mode {
     polarity: Polarity::IdleLow,
     phase: Phase::CaptureOnFirstTransition,
 };
 
-pub mod devices {
-    pub struct Ws2812;
-    pub struct Sk6812w;
-}
+pub use crate::devices;
+use crate::devices::Device;
+use crate::encoder::{Encoder, Word};
 
-pub struct Ws2812<'a, SPI, DEVICE = devices::Ws2812> {
+/// Driver rendering into a buffer of `W` words, e.g. `u16` for spis with 16
+/// bit frames
+pub struct Ws2812<'a, SPI, DEVICE = devices::Ws2812, W = u8>
+where
+    DEVICE: Device,
+{
     spi: SPI,
-    data: &'a mut [u8],
-    index: usize,
-    device: PhantomData<DEVICE>,
+    data: &'a mut [W],
+    encoder: Encoder<DEVICE>,
 }
 
-impl<'a, SPI, E> Ws2812<'a, SPI>
+impl<'a, SPI, W, E> Ws2812<'a, SPI, devices::Ws2812, W>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<W, Error = E>,
+    W: Word,
 {
     /// Use ws2812 devices via spi
     ///
@@ -45,24 +53,27 @@ where
     ///
     /// You may need to look at the datasheet and your own hal to verify this.
     ///
-    /// You need to provide a buffer `data`, whoose length is at least 12 * the
-    /// length of the led strip + 20 byes (or 40, if using the `mosi_idle_high` feature)
+    /// You need to provide a buffer `data`, whose length is at least 12 * the
+    /// length of the led strip + the reset bytes of the timing, twice if using the
+    /// `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size). For larger words, see
+    /// [`buffer_words`](crate::buffer_words)
     ///
     /// Please ensure that the mcu is pretty fast, otherwise weird timing
     /// issues will occur
-    pub fn new(spi: SPI, data: &'a mut [u8]) -> Self {
+    pub fn new(spi: SPI, data: &'a mut [W]) -> Self {
         Self {
             spi,
             data,
-            index: 0,
-            device: PhantomData {},
+            encoder: Encoder::new(),
         }
     }
 }
 
-impl<'a, SPI, E> Ws2812<'a, SPI, devices::Sk6812w>
+impl<'a, SPI, W, E> Ws2812<'a, SPI, devices::Sk6812w, W>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<W, Error = E>,
+    W: Word,
 {
     /// Use sk6812w devices via spi
     ///
@@ -70,111 +81,74 @@ where
     ///
     /// You may need to look at the datasheet and your own hal to verify this.
     ///
-    /// You need to provide a buffer `data`, whoose length is at least 12 * the
-    /// length of the led strip
+    /// You need to provide a buffer `data`, whose length is at least 16 * the
+    /// length of the led strip + the reset bytes of the timing, twice if using the
+    /// `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size). For larger words, see
+    /// [`buffer_words`](crate::buffer_words)
     ///
     /// Please ensure that the mcu is pretty fast, otherwise weird timing
     /// issues will occur
     // The spi frequencies are just the limits, the available timing data isn't
     // complete
-    pub fn new_sk6812w(spi: SPI, data: &'a mut [u8]) -> Self {
+    pub fn new_sk6812w(spi: SPI, data: &'a mut [W]) -> Self {
         Self {
             spi,
             data,
-            index: 0,
-            device: PhantomData {},
+            encoder: Encoder::new(),
         }
     }
 }
 
-impl<'a, SPI, D, E> Ws2812<'a, SPI, D>
+impl<'a, SPI, D, W, E> Ws2812<'a, SPI, D, W>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<W, Error = E>,
+    D: Device,
+    W: Word,
 {
-    /// Write a single byte for ws2812 devices
-    fn write_byte(&mut self, mut data: u8) {
-        // Send two bits in one spi byte. High time first, then the low time
-        // The maximum for T0H is 500ns, the minimum for one bit 1063 ns.
-        // These result in the upper and lower spi frequency limits
-        let patterns = [0b1000_1000, 0b1000_1110, 0b11101000, 0b11101110];
-        for _ in 0..4 {
-            let bits = (data & 0b1100_0000) >> 6;
-            self.data[self.index] = patterns[bits as usize];
-            self.index += 1;
-            data <<= 2;
-        }
-    }
-
-    fn send_data(&mut self) -> Result<(), E> {
-        // We introduce an offset in the fifo here, so there's always one byte in transit
-        // Some MCUs (like the stm32f1) only a one byte fifo, which would result
-        // in overrun error if two bytes need to be stored
-        block!(self.spi.send(0))?;
-        if cfg!(feature = "mosi_idle_high") {
-            for _ in 0..140 {
-                block!(self.spi.send(0))?;
-                block!(self.spi.read())?;
-            }
-        }
-        for b in self.data[..self.index].iter() {
-            block!(self.spi.send(*b))?;
-            block!(self.spi.read())?;
-        }
-        for _ in 0..140 {
-            block!(self.spi.send(0))?;
-            block!(self.spi.read())?;
-        }
-        // Now, resolve the offset we introduced at the beginning
-        block!(self.spi.read())?;
-        Ok(())
+    /// Use any supported device via spi
+    ///
+    /// The spi bit pattern & reset time are generated from the timing of the
+    /// device, for an spi running at `spi_freq` Hz.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least
+    /// [`buffer_size_for_freq`](crate::buffer_size_for_freq) for the amount of
+    /// leds & `spi_freq` (divided by the bytes per word, rounded up)
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn new_device(spi: SPI, data: &'a mut [W], spi_freq: u32) -> Option<Self> {
+        Some(Self {
+            spi,
+            data,
+            encoder: Encoder::for_freq(spi_freq)?,
+        })
     }
-}
 
-impl<'a, SPI, E> SmartLedsWrite for Ws2812<'a, SPI>
-where
-    SPI: FullDuplex<u8, Error = E>,
-{
-    type Error = E;
-    type Color = RGB8;
-    /// Write all the items of an iterator to a ws2812 strip
-    fn write<T, I>(&mut self, iterator: T) -> Result<(), E>
-    where
-        T: Iterator<Item = I>,
-        I: Into<Self::Color>,
-    {
-        self.index = 0;
-
-        for item in iterator {
-            let item = item.into();
-            self.write_byte(item.g);
-            self.write_byte(item.r);
-            self.write_byte(item.b);
-        }
-        self.send_data()
-    }
+    encoder_builders!(D);
 }
 
-impl<'a, SPI, E> SmartLedsWrite for Ws2812<'a, SPI, devices::Sk6812w>
+impl<'a, SPI, D, W, E> SmartLedsWrite for Ws2812<'a, SPI, D, W>
 where
-    SPI: FullDuplex<u8, Error = E>,
+    SPI: spi::Write<W, Error = E>,
+    D: Device,
+    W: Word,
 {
-    type Error = E;
-    type Color = RGBW<u8, u8>;
+    type Error = Error<E>;
+    type Color = D::Color;
     /// Write all the items of an iterator to a ws2812 strip
-    fn write<T, I>(&mut self, iterator: T) -> Result<(), E>
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the frame doesn't fit into the
+    /// buffer.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
     where
-        T: Iterator<Item = I>,
+        T: IntoIterator<Item = I>,
         I: Into<Self::Color>,
     {
-        self.index = 0;
-
-        for item in iterator {
-            let item = item.into();
-            self.write_byte(item.g);
-            self.write_byte(item.r);
-            self.write_byte(item.b);
-            self.write_byte(item.a.0);
-        }
-        self.send_data()
+        let len = self
+            .encoder
+            .render_words(iterator, self.data)
+            .map_err(Error::cast)?;
+        self.spi.write(&self.data[..len])?;
+        self.spi.flush().map_err(Error::Spi)
     }
 }
@@ -0,0 +1,122 @@
+//! Async version of the prerendered variant.
+//!
+//! The whole frame, including the reset time, is rendered into the buffer and
+//! then handed to the spi in a single transfer. This way, the HAL can send it
+//! using DMA, while other tasks keep running.
+
+use embedded_hal_async::spi::SpiBus;
+
+use smart_leds_trait::SmartLedsWriteAsync;
+
+use crate::devices::Device;
+use crate::encoder::Encoder;
+use crate::Error;
+pub use crate::{devices, MODE};
+
+pub struct Ws2812<'a, SPI, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    spi: SPI,
+    data: &'a mut [u8],
+    encoder: Encoder<DEVICE>,
+}
+
+impl<'a, SPI, E> Ws2812<'a, SPI>
+where
+    SPI: SpiBus<u8, Error = E>,
+{
+    /// Use ws2812 devices via spi
+    ///
+    /// The SPI bus should run within 2 MHz to 3.8 MHz
+    ///
+    /// You may need to look at the datasheet and your own hal to verify this.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least 12 * the
+    /// length of the led strip + the reset bytes of the timing, twice if using the
+    /// `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size)
+    pub fn new(spi: SPI, data: &'a mut [u8]) -> Self {
+        Self {
+            spi,
+            data,
+            encoder: Encoder::new(),
+        }
+    }
+}
+
+impl<'a, SPI, E> Ws2812<'a, SPI, devices::Sk6812w>
+where
+    SPI: SpiBus<u8, Error = E>,
+{
+    /// Use sk6812w devices via spi
+    ///
+    /// The SPI bus should run within 2.3 MHz to 3.8 MHz at least.
+    ///
+    /// You may need to look at the datasheet and your own hal to verify this.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least 16 * the
+    /// length of the led strip + the reset bytes of the timing, twice if using the
+    /// `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size)
+    // The spi frequencies are just the limits, the available timing data isn't
+    // complete
+    pub fn new_sk6812w(spi: SPI, data: &'a mut [u8]) -> Self {
+        Self {
+            spi,
+            data,
+            encoder: Encoder::new(),
+        }
+    }
+}
+
+impl<'a, SPI, D, E> Ws2812<'a, SPI, D>
+where
+    SPI: SpiBus<u8, Error = E>,
+    D: Device,
+{
+    /// Use any supported device via spi
+    ///
+    /// The spi bit pattern & reset time are generated from the timing of the
+    /// device, for an spi running at `spi_freq` Hz.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least
+    /// [`buffer_size_for_freq`](crate::buffer_size_for_freq) for the amount of
+    /// leds & `spi_freq`
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn new_device(spi: SPI, data: &'a mut [u8], spi_freq: u32) -> Option<Self> {
+        Some(Self {
+            spi,
+            data,
+            encoder: Encoder::for_freq(spi_freq)?,
+        })
+    }
+
+    encoder_builders!(D);
+}
+
+impl<'a, SPI, D, E> SmartLedsWriteAsync for Ws2812<'a, SPI, D>
+where
+    SPI: SpiBus<u8, Error = E>,
+    D: Device,
+{
+    type Error = Error<E>;
+    type Color = D::Color;
+    /// Write all the items of an iterator to a ws2812 strip
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the frame doesn't fit into the
+    /// buffer.
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let len = self
+            .encoder
+            .render(iterator, self.data)
+            .map_err(Error::cast)?;
+        self.spi.write(&self.data[..len]).await?;
+        self.spi.flush().await.map_err(Error::Spi)
+    }
+}
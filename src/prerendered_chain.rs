@@ -0,0 +1,145 @@
+//! Prerendered version of the chain variant.
+//!
+//! This works like the `prerendered` variant, but for chains consisting of
+//! different devices. The needed buffer size can be calculated with
+//! [`buffer_size`].
+
+use smart_leds_trait::{SmartLedsWrite, RGBW};
+
+pub use crate::chain::{buffer_size, buffer_size_with_idle, Format, Segment};
+use crate::encoder::Encoder;
+use crate::spi::{self, MosiIdle};
+use crate::timing::Timing;
+use crate::Error;
+
+pub struct Ws2812<'a, 's, SPI> {
+    spi: SPI,
+    data: &'a mut [u8],
+    index: usize,
+    segments: &'s [Segment],
+    encoder: Encoder,
+}
+
+impl<'a, 's, SPI, E> Ws2812<'a, 's, SPI>
+where
+    SPI: spi::Write<Error = E>,
+{
+    /// Use a chain of different devices via spi
+    ///
+    /// The SPI bus should run within 2.3 MHz to 3.8 MHz, if the chain only
+    /// contains ws2812 & sk6812w leds.
+    ///
+    /// You need to provide a buffer `data`, whose length is at least
+    /// [`buffer_size`] for the segments & timing.
+    pub fn new(spi: SPI, segments: &'s [Segment], data: &'a mut [u8]) -> Self {
+        Self {
+            spi,
+            data,
+            index: 0,
+            segments,
+            encoder: Encoder::new(),
+        }
+    }
+
+    /// Use a different spi bit pattern
+    ///
+    /// This is needed if the spi doesn't run within the frequency range
+    /// supported by [`Timing::DEFAULT`].
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.encoder = self.encoder.with_timing(timing);
+        self
+    }
+
+    /// Use a different MOSI idle level
+    ///
+    /// If MOSI idles high, a reset is sent before each frame as well. By
+    /// default this depends on the `mosi_idle_high` feature.
+    ///
+    /// The buffer size for other idle levels is calculated with
+    /// [`buffer_size_with_idle`].
+    pub fn with_mosi_idle(mut self, idle: MosiIdle) -> Self {
+        self.encoder = self.encoder.with_mosi_idle(idle);
+        self
+    }
+
+    /// Invert the output, e.g. for inverting level shifters
+    ///
+    /// Every spi bit is complemented & the reset is sent as high bytes, so
+    /// MOSI should idle high (see [`MosiIdle`]). The buffer size is
+    /// calculated with [`buffer_size_with_idle`].
+    pub fn with_inverted_output(mut self, inverted: bool) -> Self {
+        self.encoder = self.encoder.with_inverted_output(inverted);
+        self
+    }
+
+    /// Write a single byte for ws2812 devices
+    fn write_byte(&mut self, data: u8) {
+        let bits = self.encoder.timing().bits() as usize;
+        if let Some(out) = self.data.get_mut(self.index..self.index + bits) {
+            self.encoder.encode_byte(data, out);
+        }
+        // Keep counting if the buffer is too small, to report the needed size
+        self.index += bits;
+    }
+
+    /// Write the low time needed for a reset
+    fn write_reset(&mut self) {
+        let reset_bytes = self.encoder.reset_size();
+        let level = self.encoder.reset_level();
+        if let Some(out) = self.data.get_mut(self.index..self.index + reset_bytes) {
+            out.fill(level);
+        }
+        self.index += reset_bytes;
+    }
+
+    fn send_data(&mut self) -> Result<(), Error<E>> {
+        let data = self.data.get(..self.index).ok_or(Error::BufferTooSmall {
+            needed: self.index,
+            available: self.data.len(),
+        })?;
+        self.spi.write(data)?;
+        self.spi.flush().map_err(Error::Spi)
+    }
+}
+
+impl<'a, 's, SPI, E> SmartLedsWrite for Ws2812<'a, 's, SPI>
+where
+    SPI: spi::Write<Error = E>,
+{
+    type Error = Error<E>;
+    type Color = RGBW<u8, u8>;
+    /// Write all the items of an iterator to the chain
+    ///
+    /// Each segment takes as many items as it has leds. Fails with
+    /// [`Error::BufferTooSmall`], if the frame doesn't fit into the buffer, or
+    /// with [`Error::TooManyPixels`], if there are more items than leds in all
+    /// segments. Nothing is sent in both cases.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.index = 0;
+        if self.encoder.pre_reset() {
+            self.write_reset();
+        }
+
+        let mut iterator = iterator.into_iter();
+        for segment in self.segments {
+            let format = segment.format();
+            let mut data = [0; 4];
+            let data = &mut data[..format.bytes_per_led()];
+            for item in iterator.by_ref().take(segment.len()) {
+                format.write_led(item.into(), data);
+                for byte in data.iter() {
+                    self.write_byte(*byte);
+                }
+            }
+        }
+        if iterator.next().is_some() {
+            return Err(Error::TooManyPixels);
+        }
+        self.write_reset();
+        self.send_data()
+    }
+}
@@ -0,0 +1,209 @@
+//! DMA version of the prerendered variant.
+//!
+//! The data is rendered into a `'static` buffer, which is then handed over to a
+//! DMA capable spi. While one frame is being sent, the next one can already be
+//! rendered into a second buffer.
+//!
+//! There's no common trait for DMA transfers, so the HAL needs to be connected
+//! by implementing [`DmaWrite`] & [`DmaTransfer`]. The rendered data is passed
+//! as an [`embedded_dma::ReadBuffer`], which most DMA implementations accept.
+
+use embedded_dma::ReadBuffer;
+
+use core::convert::Infallible;
+use core::ops::DerefMut;
+
+use crate::devices::Device;
+use crate::encoder::Encoder;
+use crate::Error;
+pub use crate::{devices, MODE};
+
+/// Spi peripherals, which can send a buffer using DMA
+pub trait DmaWrite<B>: Sized
+where
+    B: ReadBuffer<Word = u8>,
+{
+    type Transfer: DmaTransfer<Buffer = B, Spi = Self>;
+
+    /// Start sending the whole buffer
+    fn start_write(self, buffer: B) -> Self::Transfer;
+}
+
+/// A running DMA transfer, started by [`DmaWrite::start_write`]
+pub trait DmaTransfer {
+    type Buffer;
+    type Spi;
+
+    /// Check if all data was sent
+    fn is_done(&self) -> bool;
+
+    /// Block until all data was sent, then release the buffer & spi
+    fn wait(self) -> (Self::Buffer, Self::Spi);
+}
+
+pub struct Ws2812<B, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    buffer: B,
+    index: usize,
+    encoder: Encoder<DEVICE>,
+}
+
+impl<B> Ws2812<B>
+where
+    B: DerefMut + ReadBuffer<Word = u8>,
+    B::Target: AsMut<[u8]>,
+{
+    /// Use ws2812 devices via spi
+    ///
+    /// The SPI bus should run within 2 MHz to 3.8 MHz
+    ///
+    /// You may need to look at the datasheet and your own hal to verify this.
+    ///
+    /// You need to provide a `'static` buffer, whose length is at least 12 *
+    /// the length of the led strip + the reset bytes of the timing, twice if
+    /// using the `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size)
+    pub fn new(buffer: B) -> Self {
+        Self {
+            buffer,
+            index: 0,
+            encoder: Encoder::new(),
+        }
+    }
+}
+
+impl<B> Ws2812<B, devices::Sk6812w>
+where
+    B: DerefMut + ReadBuffer<Word = u8>,
+    B::Target: AsMut<[u8]>,
+{
+    /// Use sk6812w devices via spi
+    ///
+    /// The SPI bus should run within 2.3 MHz to 3.8 MHz at least.
+    ///
+    /// You may need to look at the datasheet and your own hal to verify this.
+    ///
+    /// You need to provide a `'static` buffer, whose length is at least 16 *
+    /// the length of the led strip + the reset bytes of the timing, twice if
+    /// using the `mosi_idle_high` feature (140 bytes each by default),
+    /// see [`buffer_size`](crate::buffer_size)
+    // The spi frequencies are just the limits, the available timing data isn't
+    // complete
+    pub fn new_sk6812w(buffer: B) -> Self {
+        Self {
+            buffer,
+            index: 0,
+            encoder: Encoder::new(),
+        }
+    }
+}
+
+impl<B, D> Ws2812<B, D>
+where
+    B: DerefMut + ReadBuffer<Word = u8>,
+    B::Target: AsMut<[u8]>,
+    D: Device,
+{
+    /// Use any supported device via spi
+    ///
+    /// The spi bit pattern & reset time are generated from the timing of the
+    /// device, for an spi running at `spi_freq` Hz.
+    ///
+    /// You need to provide a `'static` buffer, whose length is at least
+    /// [`buffer_size_for_freq`](crate::buffer_size_for_freq) for the amount of
+    /// leds & `spi_freq`
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency.
+    pub fn new_device(buffer: B, spi_freq: u32) -> Option<Self> {
+        Some(Self {
+            buffer,
+            index: 0,
+            encoder: Encoder::for_freq(spi_freq)?,
+        })
+    }
+
+    encoder_builders!(D);
+
+    /// Start sending the rendered data
+    ///
+    /// The buffer & spi are returned by [`Transfer::wait`], once all data was
+    /// sent.
+    pub fn start_transfer<SPI>(self, spi: SPI) -> Transfer<SPI::Transfer>
+    where
+        SPI: DmaWrite<Self>,
+    {
+        Transfer {
+            transfer: spi.start_write(self),
+        }
+    }
+
+    /// Release the buffer
+    pub fn free(self) -> B {
+        self.buffer
+    }
+}
+
+impl<B, D> Ws2812<B, D>
+where
+    B: DerefMut + ReadBuffer<Word = u8>,
+    B::Target: AsMut<[u8]>,
+    D: Device,
+{
+    /// Render all the items of an iterator for a ws2812 strip
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the frame doesn't fit into the
+    /// buffer. Nothing should be sent in this case.
+    pub fn render<T, I>(&mut self, iterator: T) -> Result<(), Error<Infallible>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<D::Color>,
+    {
+        match self.encoder.render(iterator, self.buffer.as_mut()) {
+            Ok(len) => {
+                self.index = len;
+                Ok(())
+            }
+            Err(e) => {
+                // Don't hand out more than the buffer via `ReadBuffer`
+                self.index = 0;
+                Err(e)
+            }
+        }
+    }
+}
+
+// Only the rendered part of the buffer is sent
+unsafe impl<B, D> ReadBuffer for Ws2812<B, D>
+where
+    B: ReadBuffer<Word = u8>,
+    D: Device,
+{
+    type Word = u8;
+
+    unsafe fn read_buffer(&self) -> (*const u8, usize) {
+        let (ptr, _) = self.buffer.read_buffer();
+        (ptr, self.index)
+    }
+}
+
+/// A frame being sent, started by [`Ws2812::start_transfer`]
+pub struct Transfer<T> {
+    transfer: T,
+}
+
+impl<T> Transfer<T>
+where
+    T: DmaTransfer,
+{
+    /// Check if the whole frame was sent
+    pub fn is_done(&self) -> bool {
+        self.transfer.is_done()
+    }
+
+    /// Block until the whole frame was sent, then release the buffer & spi
+    pub fn wait(self) -> (T::Buffer, T::Spi) {
+        self.transfer.wait()
+    }
+}
@@ -1,129 +1,707 @@
 //! This prerenders the data, so that no calculations have to be performed while sending the data.
 //!
+//! The leds are stored as the encoded spi data in an array owned by the
+//! driver, so sending a frame is a single spi write. Setting or reading a led
+//! color encodes or decodes its data.
+//!
 //! This approach minimizes timing issues, at the cost of much higher ram usage.
-//! It also increases the needed time.
 
 use embedded_hal as hal;
 
-use hal::spi::{FullDuplex, Mode, Phase, Polarity};
+use hal::spi::{Mode, Phase, Polarity};
+
+use core::ops::{Bound, Range, RangeBounds};
+use core::slice::ChunksExactMut;
 
-use smart_leds_trait::{RGB, RGB8};
+use smart_leds_trait::SmartLedsWrite;
 
-use nb;
-use nb::block;
+use crate::color_order::ColorOrder;
+pub use crate::devices;
+use crate::devices::Device;
+use crate::encoder::Encoder;
+use crate::spi::{self, MosiIdle};
+use crate::timing::Timing;
+use crate::Error;
 
 /// SPI mode that can be used for this crate
 ///
 /// Provided for convenience
 /// Doesn't really matter
+///
+/// The leds only see MOSI, which should idle low (or high with inverted
+/// output). Otherwise configure the idle level with `with_mosi_idle`.
 pub const MODE: Mode = Mode {
     polarity: Polarity::IdleLow,
     phase: Phase::CaptureOnFirstTransition,
 };
 
-pub struct Ws2812<SPI, const N: usize>
+/// Driver for `N` leds, storing their spi data in a `L` byte array
+///
+/// `L` needs to be at least [`buffer_size`](crate::buffer_size) for the
+/// device & timing, e.g. `Ws2812<_, 10, { buffer_size::<devices::Ws2812>(10)
+/// }>` for 10 ws2812 leds. For ws2812 leds with the default timing this is `N
+/// * 12 + 140` (`N * 12 + 280` with the `mosi_idle_high` feature).
+pub struct Ws2812<SPI, const N: usize, const L: usize, DEVICE = devices::Ws2812>
 where
-    [u8; N * 3]: Sized,
+    DEVICE: Device,
 {
     spi: SPI,
-    data: [u8; N * 3],
+    data: [u8; L],
+    encoder: Encoder<DEVICE>,
 }
 
-impl<SPI, E, const N: usize> Ws2812<SPI, N>
+impl<SPI, E, const N: usize, const L: usize> Ws2812<SPI, N, L>
 where
-    SPI: FullDuplex<u8, Error = E>,
-    [u8; N * 3]: Sized,
+    SPI: spi::Write<Error = E>,
 {
-    // Byte amount to represent a color. Must be same value as array size factor.
-    const CHANNEL_AMOUNT: usize = 3;
-
     /// Use ws2812 devices via spi
     ///
     /// The SPI bus should run within 2 MHz to 3.8 MHz
     ///
     /// You may need to look at the datasheet and your own hal to verify this.
     ///
-    /// You need to provide a buffer `data`, whose length is at least 12 * the
-    /// length of the led strip + 20 byes (or 40, if using the `mosi_idle_high` feature)
-    ///
-    /// Please ensure that the mcu is pretty fast, otherwise weird timing
-    /// issues will occur
+    /// The spi data of the `N` leds is stored in the driver itself, see
+    /// [`Ws2812`] for the needed buffer length `L`, which is checked at compile
+    /// time. All leds are off initially.
     pub fn new(spi: SPI) -> Self {
-        Self {
-            spi,
-            data: [0; N * 3],
-        }
+        #[allow(clippy::let_unit_value)]
+        let () = Self::LENGTH_CHECK;
+        Self::with_device_encoder(spi, Encoder::new())
     }
 }
 
-impl<SPI, E, const N: usize> Ws2812<SPI, N>
+impl<SPI, E, const N: usize, const L: usize> Ws2812<SPI, N, L, devices::Sk6812w>
 where
-    SPI: FullDuplex<u8, Error = E>,
-    [u8; N * 3]: Sized,
+    SPI: spi::Write<Error = E>,
 {
-    pub fn value_at(&self, index: usize) -> u8 {
-        self.data[index]
+    /// Use sk6812w devices via spi
+    ///
+    /// The SPI bus should run within 2.3 MHz to 3.8 MHz at least.
+    ///
+    /// You may need to look at the datasheet and your own hal to verify this.
+    ///
+    /// The buffer length `L` needs to be at least `N * 16 + 140` (`N * 16 +
+    /// 280` with the `mosi_idle_high` feature), which is checked at compile
+    /// time, see [`buffer_size`](crate::buffer_size). All leds are off
+    /// initially.
+    // The spi frequencies are just the limits, the available timing data isn't
+    // complete
+    pub fn new_sk6812w(spi: SPI) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::LENGTH_CHECK;
+        Self::with_device_encoder(spi, Encoder::new())
     }
+}
+
+impl<SPI, E, const N: usize, const L: usize, D> Ws2812<SPI, N, L, D>
+where
+    SPI: spi::Write<Error = E>,
+    D: Device,
+{
+    // Evaluated when referenced in the constructors, so a too small buffer
+    // fails to compile
+    const LENGTH_CHECK: () = assert!(
+        L >= Self::frame_size(&Timing::DEFAULT, MosiIdle::DEFAULT, false),
+        "The buffer length L is too small for N leds"
+    );
 
-    pub fn led_color(&self, index: usize) -> RGB8 {
-        let offset = index * 3;
-        RGB::new(
-            self.data[offset + 1],
-            self.data[offset],
-            self.data[offset + 2],
-        )
+    /// Use any supported device via spi
+    ///
+    /// The spi bit pattern & reset time are generated from the timing of the
+    /// device, for an spi running at `spi_freq` Hz. All leds are off
+    /// initially.
+    ///
+    /// Returns `None`, if the device can't be driven at this frequency or the
+    /// buffer length `L` is too small for the generated timing.
+    pub fn new_device(spi: SPI, spi_freq: u32) -> Option<Self> {
+        let encoder = Encoder::for_freq(spi_freq)?;
+        if encoder.frame_size(N) > L {
+            return None;
+        }
+        Some(Self::with_device_encoder(spi, encoder))
     }
 
-    pub fn set_led_color(&mut self, index: usize, color: RGB8) {
-        let offset = index * Self::CHANNEL_AMOUNT;
-        self.data[offset] = color.g;
-        self.data[offset + 1] = color.r;
-        self.data[offset + 2] = color.b;
+    fn with_device_encoder(spi: SPI, encoder: Encoder<D>) -> Self {
+        let mut this = Self {
+            spi,
+            data: [0; L],
+            encoder,
+        };
+        this.render_off();
+        this
     }
-}
 
-impl<SPI, E, const N: usize> Ws2812<SPI, N>
-where
-    SPI: FullDuplex<u8, Error = E>,
-    [u8; N * 3]: Sized,
-{
-    /// Write a single byte for ws2812 devices to spi
-    fn send_byte(&mut self, mut value: u8) -> Result<(), E> {
-        // Send two bits in one spi byte. High time first, then the low time
-        // The maximum for T0H is 500ns, the minimum for one bit 1063 ns.
-        // These result in the upper and lower spi frequency limits
-        let patterns = [0b1000_1000, 0b1000_1110, 0b11101000, 0b11101110];
-        for _ in 0..4 {
-            let bits = (value & 0b1100_0000) >> 6;
-            block!(self.spi.send(patterns[bits as usize]))?;
-            value <<= 2;
+    /// Use the settings of an [`Encoder`]
+    ///
+    /// All leds are turned off.
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the buffer is too small for
+    /// the settings.
+    pub fn with_encoder(mut self, encoder: Encoder<D>) -> Result<Self, Error<E>> {
+        self.set_encoder(encoder)?;
+        Ok(self)
+    }
+
+    /// Use a different spi bit pattern
+    ///
+    /// This is needed if the spi doesn't run within the frequency range
+    /// supported by [`Timing::DEFAULT`]. All leds are turned off.
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the buffer is too small for
+    /// the timing.
+    pub fn with_timing(mut self, timing: Timing) -> Result<Self, Error<E>> {
+        let encoder = self.encoder.with_timing(timing);
+        self.set_encoder(encoder)?;
+        Ok(self)
+    }
+
+    /// Use a different MOSI idle level
+    ///
+    /// If MOSI idles high, a reset is sent before each frame as well. By
+    /// default this depends on the `mosi_idle_high` feature. All leds are
+    /// turned off.
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the buffer is too small for
+    /// the additional reset (see [`buffer_size_with_idle`](crate::buffer_size_with_idle)).
+    pub fn with_mosi_idle(mut self, idle: MosiIdle) -> Result<Self, Error<E>> {
+        let encoder = self.encoder.with_mosi_idle(idle);
+        self.set_encoder(encoder)?;
+        Ok(self)
+    }
+
+    /// Invert the output, e.g. for inverting level shifters
+    ///
+    /// Every spi bit is complemented & the reset is sent as high bytes, so
+    /// MOSI should idle high (see [`MosiIdle`]). All leds are turned off.
+    ///
+    /// Fails with [`Error::BufferTooSmall`], if the buffer is too small for
+    /// an additional reset.
+    pub fn with_inverted_output(mut self, inverted: bool) -> Result<Self, Error<E>> {
+        let encoder = self.encoder.with_inverted_output(inverted);
+        self.set_encoder(encoder)?;
+        Ok(self)
+    }
+
+    /// Send the color channels in a different order
+    ///
+    /// By default, the channels are sent in the order of the device
+    /// ([`Device::ORDER`]). All leds are turned off.
+    pub fn with_color_order(mut self, order: ColorOrder) -> Self {
+        self.encoder = self.encoder.with_color_order(order);
+        self.render_off();
+        self
+    }
+
+    /// Use different device specific settings
+    ///
+    /// These are sent at the start of every frame, e.g. the drive currents
+    /// of [`devices::Tm1814`].
+    pub fn with_config(mut self, config: D::Config) -> Self {
+        self.set_config(config);
+        self
+    }
+
+    /// Change the device specific settings
+    pub fn set_config(&mut self, config: D::Config) {
+        self.encoder.set_config(config);
+        self.render_header();
+    }
+
+    /// Get a single byte of led data, in sending order
+    pub fn value_at(&self, index: usize) -> Result<u8, Error<E>> {
+        if index >= N * D::BYTES_PER_LED {
+            return Err(Error::IndexOutOfRange);
+        }
+        Ok(self
+            .encoder
+            .decode_byte(&self.data[self.symbol_range(D::HEADER_BYTES + index)]))
+    }
+
+    pub fn led_color(&self, index: usize) -> Result<D::Color, Error<E>> {
+        if index >= N {
+            return Err(Error::IndexOutOfRange);
+        }
+        let size = self.encoder.led_size();
+        let start = self.led_range().start + index * size;
+        Ok(self.encoder.decode_led(&self.data[start..start + size]))
+    }
+
+    pub fn set_led_color(&mut self, index: usize, color: D::Color) -> Result<(), Error<E>> {
+        if index >= N {
+            return Err(Error::IndexOutOfRange);
+        }
+        self.leds(index, index + 1).set_led_color(0, color);
+        Ok(())
+    }
+
+    /// Set all leds to the same color
+    pub fn fill(&mut self, color: D::Color) {
+        self.leds(0, N).fill(color)
+    }
+
+    /// Turn all leds off
+    pub fn clear(&mut self) {
+        self.leds(0, N).clear()
+    }
+
+    /// Iterate over all leds, to get or set their color
+    pub fn iter_mut(&mut self) -> IterMut<'_, D> {
+        self.leds(0, N).into_iter()
+    }
+
+    /// Get a range of leds, e.g. `slice_mut(2..5)`
+    pub fn slice_mut<R: RangeBounds<usize>>(&mut self, range: R) -> Result<Leds<'_, D>, Error<E>> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => Some(*start),
+            Bound::Excluded(start) => start.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.checked_add(1),
+            Bound::Excluded(end) => Some(*end),
+            Bound::Unbounded => Some(N),
+        };
+        let (start, end) = start.zip(end).ok_or(Error::IndexOutOfRange)?;
+        if start > end || end > N {
+            return Err(Error::IndexOutOfRange);
+        }
+        Ok(self.leds(start, end))
+    }
+
+    /// The leds from `start` to `end`, which need to be in range
+    fn leds(&mut self, start: usize, end: usize) -> Leds<'_, D> {
+        let size = self.encoder.led_size();
+        let offset = self.led_range().start;
+        Leds {
+            symbols: &mut self.data[offset + start * size..offset + end * size],
+            encoder: self.encoder,
         }
+    }
 
+    /// Amount of spi bytes for a whole frame with `timing`, `idle` &
+    /// `inverted` output
+    const fn frame_size(timing: &Timing, idle: MosiIdle, inverted: bool) -> usize {
+        crate::buffer_size_with_idle::<D>(N, timing, idle, inverted)
+    }
+
+    /// Switch to the settings of `encoder` & turn all leds off
+    ///
+    /// Fails without changing anything, if the frame doesn't fit into the
+    /// buffer.
+    fn set_encoder(&mut self, encoder: Encoder<D>) -> Result<(), Error<E>> {
+        let needed = encoder.frame_size(N);
+        if needed > L {
+            return Err(Error::BufferTooSmall {
+                needed,
+                available: L,
+            });
+        }
+        self.encoder = encoder;
+        self.render_off();
         Ok(())
     }
 
+    /// Range of the spi data for all leds
+    fn led_range(&self) -> Range<usize> {
+        let start = self.symbol_range(D::HEADER_BYTES).start;
+        start
+            ..self
+                .symbol_range(D::HEADER_BYTES + N * D::BYTES_PER_LED)
+                .start
+    }
+
+    /// Range of the spi data for a single byte of the frame, header included
+    fn symbol_range(&self, index: usize) -> Range<usize> {
+        let bits = self.encoder.timing().bits() as usize;
+        let start = if self.encoder.pre_reset() {
+            self.encoder.reset_size()
+        } else {
+            0
+        };
+        start + index * bits..start + (index + 1) * bits
+    }
+
+    /// Write the device specific header sent at the start of each frame
+    fn render_header(&mut self) {
+        let range = self.symbol_range(0).start..self.led_range().start;
+        self.encoder.encode_header(&mut self.data[range]);
+    }
+
+    /// Render the whole frame with all leds turned off
+    fn render_off(&mut self) {
+        // The resets are the idle level of the data line
+        self.data.fill(self.encoder.reset_level());
+        self.render_header();
+        self.clear();
+    }
+}
+
+impl<SPI, E, const N: usize, const L: usize, D> Ws2812<SPI, N, L, D>
+where
+    SPI: spi::Write<Error = E>,
+    D: Device,
+{
     /// Send the pre rendered data to the LEDs.
-    pub fn send_data(&mut self) -> Result<(), E> {
-        // We introduce an offset in the FIFO here, so there's always one byte in transit
-        // Some MCUs (like the stm32f1) only a one byte FIFO, which would result
-        // in overrun error if two bytes need to be stored
-        block!(self.spi.send(0))?;
-        if cfg!(feature = "mosi_idle_high") {
-            for _ in 0..140 {
-                block!(self.spi.send(0))?;
-                block!(self.spi.read())?;
+    pub fn send_data(&mut self) -> Result<(), Error<E>> {
+        let size = self.encoder.frame_size(N);
+        self.spi.write(&self.data[..size])?;
+        self.spi.flush().map_err(Error::Spi)
+    }
+}
+
+impl<SPI, E, const N: usize, const L: usize, D> SmartLedsWrite for Ws2812<SPI, N, L, D>
+where
+    SPI: spi::Write<Error = E>,
+    D: Device,
+{
+    type Error = Error<E>;
+    type Color = D::Color;
+    /// Write all the items of an iterator to the leds & send them
+    ///
+    /// Leds after the last item keep their color. If there are more than `N`
+    /// items, nothing is sent & [`Error::TooManyPixels`] is returned. The
+    /// first `N` items are already set in this case, so the leds show them
+    /// with the next [`Ws2812::send_data`].
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        for (i, item) in iterator.into_iter().enumerate() {
+            if i >= N {
+                return Err(Error::TooManyPixels);
             }
+            self.set_led_color(i, item.into())?;
         }
-        for b in self.data {
-            self.send_byte(b)?;
-            block!(self.spi.read())?;
+        self.send_data()
+    }
+}
+
+/// A range of leds, see [`Ws2812::slice_mut`]
+pub struct Leds<'a, D: Device> {
+    symbols: &'a mut [u8],
+    encoder: Encoder<D>,
+}
+
+impl<'a, D: Device> Leds<'a, D> {
+    /// Amount of leds
+    pub fn len(&self) -> usize {
+        self.symbols.len() / self.encoder.led_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Panics, if `index` is out of range
+    pub fn led_color(&self, index: usize) -> D::Color {
+        let size = self.encoder.led_size();
+        self.encoder
+            .decode_led(&self.symbols[index * size..(index + 1) * size])
+    }
+
+    /// Panics, if `index` is out of range
+    pub fn set_led_color(&mut self, index: usize, color: D::Color) {
+        let size = self.encoder.led_size();
+        self.encoder
+            .encode_led(color, &mut self.symbols[index * size..(index + 1) * size])
+    }
+
+    /// Set all leds to the same color
+    pub fn fill(&mut self, color: D::Color) {
+        if self.is_empty() {
+            return;
         }
-        for _ in 0..140 {
-            block!(self.spi.send(0))?;
-            block!(self.spi.read())?;
+        // Only the first led is encoded, the others are copies
+        let size = self.encoder.led_size();
+        let (first, rest) = self.symbols.split_at_mut(size);
+        self.encoder.encode_led(color, first);
+        for symbols in rest.chunks_exact_mut(size) {
+            symbols.copy_from_slice(first);
         }
-        // Now, resolve the offset we introduced at the beginning
-        block!(self.spi.read())?;
-        Ok(())
+    }
+
+    /// Turn all leds off
+    pub fn clear(&mut self) {
+        let bits = self.encoder.timing().bits() as usize;
+        for symbols in self.symbols.chunks_exact_mut(bits) {
+            self.encoder.encode_byte(0, symbols);
+        }
+    }
+
+    /// Iterate over the leds, to get or set their color
+    pub fn iter_mut(&mut self) -> IterMut<'_, D> {
+        IterMut {
+            chunks: self.symbols.chunks_exact_mut(self.encoder.led_size()),
+            encoder: self.encoder,
+        }
+    }
+}
+
+impl<'a, D: Device> IntoIterator for Leds<'a, D> {
+    type Item = Led<'a, D>;
+    type IntoIter = IterMut<'a, D>;
+
+    fn into_iter(self) -> IterMut<'a, D> {
+        IterMut {
+            chunks: self.symbols.chunks_exact_mut(self.encoder.led_size()),
+            encoder: self.encoder,
+        }
+    }
+}
+
+/// Iterator over leds, see [`Ws2812::iter_mut`]
+pub struct IterMut<'a, D: Device> {
+    chunks: ChunksExactMut<'a, u8>,
+    encoder: Encoder<D>,
+}
+
+impl<'a, D: Device> Iterator for IterMut<'a, D> {
+    type Item = Led<'a, D>;
+
+    fn next(&mut self) -> Option<Led<'a, D>> {
+        Some(Led {
+            symbols: self.chunks.next()?,
+            encoder: self.encoder,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a, D: Device> ExactSizeIterator for IterMut<'a, D> {}
+
+/// A single led, see [`Ws2812::iter_mut`]
+pub struct Led<'a, D: Device> {
+    symbols: &'a mut [u8],
+    encoder: Encoder<D>,
+}
+
+impl<'a, D: Device> Led<'a, D> {
+    pub fn color(&self) -> D::Color {
+        self.encoder.decode_led(self.symbols)
+    }
+
+    pub fn set_color(&mut self, color: D::Color) {
+        self.encoder.encode_led(color, self.symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use smart_leds_trait::{White, RGB8, RGBW};
+
+    use super::*;
+
+    /// Collects the written spi data
+    #[derive(Default)]
+    struct Spi(Vec<u8>);
+
+    impl spi::Write for Spi {
+        type Error = ();
+
+        fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    /// Send the frame & compare it to the encoded `colors`
+    fn assert_sends<const N: usize, const L: usize, D: Device>(
+        leds: &mut Ws2812<Spi, N, L, D>,
+        colors: &[D::Color],
+    ) where
+        D::Color: Copy,
+    {
+        leds.spi.0.clear();
+        leds.send_data().unwrap();
+        let expected: Vec<u8> = leds.encoder.encode(colors.iter().copied()).collect();
+        assert_eq!(leds.spi.0, expected);
+    }
+
+    const RED: RGB8 = RGB8::new(0xff, 0, 0);
+    const BLUE: RGB8 = RGB8::new(0, 0, 0xa5);
+    const OFF: RGB8 = RGB8::new(0, 0, 0);
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn slice_mut_ranges() {
+        let mut leds = Ws2812::<_, 5, 1024>::new(Spi::default());
+        assert_eq!(leds.slice_mut(..).unwrap().len(), 5);
+        assert_eq!(leds.slice_mut(1..=3).unwrap().len(), 3);
+        assert_eq!(leds.slice_mut(4..).unwrap().len(), 1);
+        // Empty ranges are fine, even at the end
+        assert!(leds.slice_mut(2..2).unwrap().is_empty());
+        assert!(leds.slice_mut(5..).unwrap().is_empty());
+
+        assert!(matches!(leds.slice_mut(3..2), Err(Error::IndexOutOfRange)));
+        assert!(matches!(leds.slice_mut(2..6), Err(Error::IndexOutOfRange)));
+        assert!(matches!(leds.slice_mut(6..), Err(Error::IndexOutOfRange)));
+        // Would overflow when converted to an exclusive range
+        assert!(matches!(
+            leds.slice_mut(2..=usize::MAX),
+            Err(Error::IndexOutOfRange)
+        ));
+        assert!(matches!(
+            leds.slice_mut((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            Err(Error::IndexOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn too_many_pixels() {
+        let mut leds = Ws2812::<_, 3, 1024>::new(Spi::default());
+        assert!(matches!(
+            leds.write([RED, BLUE, RED, BLUE]),
+            Err(Error::TooManyPixels)
+        ));
+        // Nothing sent, but the first N leds are set
+        assert!(leds.spi.0.is_empty());
+        assert_sends(&mut leds, &[RED, BLUE, RED]);
+
+        // Leds after the last item keep their color
+        leds.write([BLUE]).unwrap();
+        assert_sends(&mut leds, &[BLUE, BLUE, RED]);
+    }
+
+    #[test]
+    fn fill_clear() {
+        let mut leds = Ws2812::<_, 5, 1024>::new(Spi::default());
+        assert_sends(&mut leds, &[OFF; 5]);
+        leds.fill(RED);
+        assert_sends(&mut leds, &[RED; 5]);
+        leds.slice_mut(1..3).unwrap().fill(BLUE);
+        assert_sends(&mut leds, &[RED, BLUE, BLUE, RED, RED]);
+        leds.slice_mut(3..).unwrap().clear();
+        assert_sends(&mut leds, &[RED, BLUE, BLUE, OFF, OFF]);
+        leds.clear();
+        assert_sends(&mut leds, &[OFF; 5]);
+
+        for (i, mut led) in leds.iter_mut().enumerate() {
+            led.set_color(RGB8::new(i as u8, 0, 0));
+        }
+        let colors: Vec<RGB8> = leds.iter_mut().map(|led| led.color()).collect();
+        assert_eq!(
+            colors,
+            (0..5).map(|i| RGB8::new(i, 0, 0)).collect::<Vec<_>>()
+        );
+        assert_sends(&mut leds, &colors);
+    }
+
+    /// Set `colors` with both idle levels & compare the frame & the decoded
+    /// colors
+    fn check_frame<const L: usize, D: Device>(
+        new: impl Fn() -> Ws2812<Spi, 4, L, D>,
+        colors: [D::Color; 4],
+    ) where
+        D::Color: Copy + Default + PartialEq + core::fmt::Debug,
+    {
+        for idle in [MosiIdle::Low, MosiIdle::High] {
+            let mut leds = new().with_mosi_idle(idle).unwrap();
+            assert_sends(&mut leds, &[D::Color::default(); 4]);
+
+            for (i, color) in colors.iter().enumerate() {
+                leds.set_led_color(i, *color).unwrap();
+                assert_eq!(leds.led_color(i).unwrap(), *color);
+            }
+            assert_sends(&mut leds, &colors);
+
+            // Single bytes of led data, after the header & pre-reset
+            let mut data = [0; devices::MAX_BYTES_PER_LED];
+            let data = &mut data[..D::BYTES_PER_LED];
+            D::write_led(colors[1], D::ORDER, data);
+            for (i, byte) in data.iter().enumerate() {
+                assert_eq!(leds.value_at(D::BYTES_PER_LED + i).unwrap(), *byte);
+            }
+            assert!(matches!(
+                leds.value_at(4 * D::BYTES_PER_LED),
+                Err(Error::IndexOutOfRange)
+            ));
+            assert!(matches!(leds.led_color(4), Err(Error::IndexOutOfRange)));
+        }
+    }
+
+    #[test]
+    fn ws2812_frame() {
+        check_frame(
+            || Ws2812::<_, 4, 1024>::new(Spi::default()),
+            [RED, BLUE, OFF, RGB8::new(1, 2, 3)],
+        );
+    }
+
+    #[test]
+    fn sk6812w_frame() {
+        let color = |r, w| RGBW {
+            r,
+            g: 2,
+            b: 3,
+            a: White(w),
+        };
+        check_frame(
+            || Ws2812::<_, 4, 1024, devices::Sk6812w>::new_sk6812w(Spi::default()),
+            [color(1, 0xff), color(0, 0), color(0xa5, 4), color(6, 7)],
+        );
+    }
+
+    #[test]
+    fn tm1814_frame() {
+        let color = |r, w| RGBW {
+            r,
+            g: 2,
+            b: 3,
+            a: White(w),
+        };
+        check_frame(
+            || {
+                Ws2812::<_, 4, 1024, devices::Tm1814>::new_device(Spi::default(), 3_000_000)
+                    .unwrap()
+                    .with_config(devices::Tm1814Current::new(1, 2, 3, 4))
+            },
+            [color(1, 0xff), color(0, 0), color(0xa5, 4), color(6, 7)],
+        );
+    }
+
+    #[test]
+    fn settings_too_large() {
+        const L: usize = crate::buffer_size_with_idle::<devices::Ws2812>(
+            3,
+            &Timing::DEFAULT,
+            MosiIdle::Low,
+            false,
+        );
+        let new = || {
+            Ws2812::<_, 3, L>::with_device_encoder(
+                Spi::default(),
+                Encoder::new().with_mosi_idle(MosiIdle::Low),
+            )
+        };
+        let too_small = Error::BufferTooSmall {
+            needed: L + Timing::DEFAULT.reset_bytes(),
+            available: L,
+        };
+        // A reset before each frame doesn't fit
+        assert_eq!(new().with_mosi_idle(MosiIdle::High).err(), Some(too_small));
+        assert_eq!(new().with_inverted_output(true).err(), Some(too_small));
+        assert!(new().with_inverted_output(false).is_ok());
+        // Neither do longer patterns
+        let timing = Timing::from_pattern(5, 1, 3).unwrap();
+        assert!(matches!(
+            new().with_timing(timing),
+            Err(Error::BufferTooSmall { available: L, .. })
+        ));
+        let timing = Timing::DEFAULT.with_reset_bytes(10);
+        let mut leds = new().with_timing(timing).unwrap();
+        leds.write([RED]).unwrap();
+        assert_sends(&mut leds, &[RED, OFF, OFF]);
     }
 }
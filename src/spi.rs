@@ -0,0 +1,214 @@
+//! Spi traits the drivers can send their data over
+//!
+//! Anything implementing the embedded-hal 1.0 `SpiBus` trait can be used
+//! directly. `SpiDevice` implementations need to be wrapped in [`Device`], the
+//! embedded-hal 0.2 `FullDuplex` trait is supported with the `hal_02` feature
+//! by wrapping it in `FullDuplex` or `Blocking`.
+//!
+//! All of them can send larger words as well, e.g. `u16` for spis with 16 bit
+//! frames.
+
+use embedded_hal as hal;
+
+/// Blocking sink for the spi data generated by the drivers, in words of
+/// `Word`
+pub trait Write<Word = u8> {
+    type Error;
+
+    /// Write all words of `data`
+    ///
+    /// This may return before the data is completely sent out.
+    fn write(&mut self, data: &[Word]) -> Result<(), Self::Error>;
+
+    /// Wait until all previously written data is sent out
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<SPI, W> Write<W> for SPI
+where
+    SPI: hal::spi::SpiBus<W>,
+    W: Copy + 'static,
+{
+    type Error = SPI::Error;
+
+    fn write(&mut self, data: &[W]) -> Result<(), Self::Error> {
+        hal::spi::SpiBus::write(self, data)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        hal::spi::SpiBus::flush(self)
+    }
+}
+
+/// Level of MOSI while the spi is idle
+///
+/// If MOSI doesn't idle at the level of the reset (low, or high with inverted
+/// output), the leds don't see the start of the next frame. So the drivers
+/// send a reset before each frame as well, which needs additional buffer space
+/// for the prerendered variants.
+///
+/// The default is `High` with the `mosi_idle_high` feature, `Low` otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MosiIdle {
+    Low,
+    High,
+}
+
+impl MosiIdle {
+    pub const DEFAULT: Self = if cfg!(feature = "mosi_idle_high") {
+        Self::High
+    } else {
+        Self::Low
+    };
+
+    /// Whether a reset is sent before each frame, for `inverted` output
+    pub(crate) const fn pre_reset(self, inverted: bool) -> bool {
+        matches!(self, Self::High) != inverted
+    }
+
+    /// Amount of resets sent per frame, for `inverted` output
+    pub(crate) const fn resets(self, inverted: bool) -> usize {
+        if self.pre_reset(inverted) {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+impl Default for MosiIdle {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Write `amount` bytes of the reset `level` (see
+/// [`Encoder::reset_level`](crate::encoder::Encoder::reset_level)) to keep the
+/// data line idle for the reset time
+pub(crate) fn write_reset<SPI: Write>(
+    spi: &mut SPI,
+    mut amount: usize,
+    level: u8,
+) -> Result<(), SPI::Error> {
+    let idle = [level; 64];
+    while amount > 0 {
+        let len = amount.min(idle.len());
+        spi.write(&idle[..len])?;
+        amount -= len;
+    }
+    Ok(())
+}
+
+/// Use an embedded-hal 1.0 `SpiDevice`
+///
+/// Every write is a separate transaction, so there may be larger gaps in
+/// between them. This works best with the `prerendered` variant, which sends
+/// all data at once.
+pub struct Device<SPI>(pub SPI);
+
+impl<SPI, W> Write<W> for Device<SPI>
+where
+    SPI: hal::spi::SpiDevice<W>,
+    W: Copy + 'static,
+{
+    type Error = SPI::Error;
+
+    fn write(&mut self, data: &[W]) -> Result<(), Self::Error> {
+        self.0.write(data)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // The transaction is already completed after each write
+        Ok(())
+    }
+}
+
+/// SPI mode for embedded-hal 0.2 peripherals
+///
+/// Same as [`crate::MODE`]
+#[cfg(feature = "hal_02")]
+pub const MODE_02: embedded_hal_0_2::spi::Mode = embedded_hal_0_2::spi::Mode {
+    polarity: embedded_hal_0_2::spi::Polarity::IdleLow,
+    phase: embedded_hal_0_2::spi::Phase::CaptureOnFirstTransition,
+};
+
+/// Use an embedded-hal 0.2 `FullDuplex` spi
+#[cfg(feature = "hal_02")]
+pub struct FullDuplex<SPI> {
+    spi: SPI,
+    in_transit: bool,
+}
+
+#[cfg(feature = "hal_02")]
+impl<SPI> FullDuplex<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self {
+            spi,
+            in_transit: false,
+        }
+    }
+
+    /// Release the wrapped spi
+    pub fn free(self) -> SPI {
+        self.spi
+    }
+}
+
+#[cfg(feature = "hal_02")]
+impl<SPI, W, E> Write<W> for FullDuplex<SPI>
+where
+    SPI: embedded_hal_0_2::spi::FullDuplex<W, Error = E>,
+    W: Copy,
+{
+    type Error = E;
+
+    fn write(&mut self, data: &[W]) -> Result<(), E> {
+        use nb::block;
+        for b in data {
+            block!(self.spi.send(*b))?;
+            // We introduce an offset in the fifo here, so there's always one byte in transit
+            // Some MCUs (like the stm32f1) only a one byte fifo, which would result
+            // in overrun error if two bytes need to be stored
+            if self.in_transit {
+                block!(self.spi.read()).ok();
+            } else {
+                self.in_transit = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), E> {
+        use nb::block;
+        // Now, resolve the offset we introduced at the beginning
+        if self.in_transit {
+            self.in_transit = false;
+            block!(self.spi.read())?;
+        }
+        Ok(())
+    }
+}
+
+/// Use an embedded-hal 0.2 blocking spi `Write`
+///
+/// The whole data is handed to the HAL at once, which allows HALs using DMA
+/// to send it without any gaps.
+#[cfg(feature = "hal_02")]
+pub struct Blocking<SPI>(pub SPI);
+
+#[cfg(feature = "hal_02")]
+impl<SPI, W, E> Write<W> for Blocking<SPI>
+where
+    SPI: embedded_hal_0_2::blocking::spi::Write<W, Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, data: &[W]) -> Result<(), E> {
+        self.0.write(data)
+    }
+
+    fn flush(&mut self) -> Result<(), E> {
+        // The blocking write only returns after everything was sent
+        Ok(())
+    }
+}
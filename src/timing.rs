@@ -0,0 +1,704 @@
+//! Generation of the spi bit patterns
+//!
+//! Every led bit is sent as a symbol of multiple spi bits: The first few spi
+//! bits are high, the rest low. How many spi bits are needed depends on the spi
+//! frequency & the timing requirements of the led chip.
+//!
+//! The resulting waveform can be checked against the chip timing with
+//! [`validate`], or at compile time with [`assert_timing!`](crate::assert_timing).
+
+use crate::devices::Device;
+
+/// Minimum reset times of common devices, in ns
+pub mod reset {
+    /// Original ws2812 & ws2812b
+    pub const WS2812: u32 = 50_000;
+    /// ws2812b-v5 & other newer variants
+    ///
+    /// See <https://blog.particle.io/2017/05/11/heads-up-ws2812b-neopixels-are-about-to-change/>
+    pub const WS2812B_V5: u32 = 280_000;
+    pub const SK6812: u32 = 80_000;
+    pub const APA106: u32 = 50_000;
+    pub const TM1814: u32 = 200_000;
+}
+
+/// Timing requirements of a led chip
+///
+/// All durations are in ns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChipTiming {
+    /// Minimum high time of a 0 bit
+    pub t0h_min: u32,
+    /// Maximum high time of a 0 bit
+    pub t0h_max: u32,
+    /// Minimum high time of a 1 bit
+    pub t1h_min: u32,
+    /// Maximum high time of a 1 bit
+    pub t1h_max: u32,
+    /// Minimum low time of a bit, which is the shorter for a 1 bit
+    pub tl_min: u32,
+    /// Minimum duration of a whole bit
+    pub bit_min: u32,
+    /// Maximum duration of a whole bit
+    pub bit_max: u32,
+    /// Minimum low time to latch the data
+    pub reset_min: u32,
+}
+
+impl ChipTiming {
+    // Timings for ws2812 from https://cpldcpu.files.wordpress.com/2014/01/ws2812_timing_table.png
+    pub const WS2812: Self = Self {
+        t0h_min: 100,
+        t0h_max: 500,
+        t1h_min: 625,
+        t1h_max: 5000,
+        // T1L is 450ns ± 150ns in the datasheet
+        tl_min: 300,
+        bit_min: 1000,
+        bit_max: 5000,
+        // Use the longer reset time of newer variants to be safe
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for ws2811 from the datasheet, TH + TL = 1.25μs ± 600ns
+    pub const WS2811: Self = Self {
+        t0h_min: 100,
+        t0h_max: 400,
+        t1h_min: 450,
+        t1h_max: 750,
+        // T1L is 650ns ± 150ns
+        tl_min: 500,
+        bit_min: 650,
+        bit_max: 1850,
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for the low speed mode of the ws2811, TH + TL = 2.5μs ± 600ns
+    pub const WS2811_SLOW: Self = Self {
+        t0h_min: 350,
+        t0h_max: 650,
+        t1h_min: 1050,
+        t1h_max: 1350,
+        // T1L is 1.3μs ± 150ns
+        tl_min: 1150,
+        bit_min: 1900,
+        bit_max: 3100,
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for ws2813 from the datasheet
+    pub const WS2813: Self = Self {
+        t0h_min: 220,
+        t0h_max: 380,
+        t1h_min: 580,
+        t1h_max: 1600,
+        tl_min: 300,
+        bit_min: 800,
+        bit_max: 2000,
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for ws2805 from the datasheet, the same as for the ws2813
+    pub const WS2805: Self = Self::WS2813;
+
+    // Timings for ws2815 from the datasheet
+    pub const WS2815: Self = Self {
+        t0h_min: 220,
+        t0h_max: 380,
+        t1h_min: 580,
+        t1h_max: 1000,
+        tl_min: 300,
+        bit_min: 800,
+        bit_max: 2000,
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for ws2816 from the datasheet, the same as for the ws2815
+    pub const WS2816: Self = Self::WS2815;
+
+    // Timings for ucs8904 from the datasheet
+    pub const UCS8904: Self = Self {
+        t0h_min: 150,
+        t0h_max: 400,
+        t1h_min: 580,
+        t1h_max: 1000,
+        tl_min: 300,
+        bit_min: 1000,
+        bit_max: 1600,
+        reset_min: reset::WS2812B_V5,
+    };
+
+    // Timings for tm1814 from the datasheet. The signal is inverted, so these
+    // are low times instead of high times
+    pub const TM1814: Self = Self {
+        t0h_min: 200,
+        t0h_max: 500,
+        t1h_min: 600,
+        t1h_max: 1000,
+        tl_min: 300,
+        bit_min: 1000,
+        bit_max: 1600,
+        reset_min: reset::TM1814,
+    };
+
+    // Timings for apa106 from the datasheet, TH + TL = 1.71μs ± 600ns
+    pub const APA106: Self = Self {
+        t0h_min: 200,
+        t0h_max: 500,
+        t1h_min: 1210,
+        t1h_max: 1510,
+        // T1L is 350ns ± 150ns
+        tl_min: 200,
+        bit_min: 1110,
+        bit_max: 2310,
+        reset_min: reset::APA106,
+    };
+
+    // Timings for sk6812 from https://cpldcpu.wordpress.com/2016/03/09/the-sk6812-another-intelligent-rgb-led/
+    pub const SK6812: Self = Self {
+        t0h_min: 150,
+        t0h_max: 450,
+        t1h_min: 550,
+        t1h_max: 5000,
+        tl_min: 300,
+        bit_min: 1000,
+        bit_max: 5000,
+        reset_min: reset::SK6812,
+    };
+}
+
+/// The spi bit pattern used for each led bit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    bits: u8,
+    zero_high: u8,
+    one_high: u8,
+    reset_bytes: usize,
+}
+
+/// Supported amounts of spi bits per led bit, ordered by preference
+const SYMBOL_BITS: [u8; 5] = [3, 4, 5, 6, 8];
+
+impl Timing {
+    /// Four spi bits per led bit, `0b1000` for a 0 & `0b1110` for a 1
+    ///
+    /// Meets the ws2812 timing with an spi frequency within 2 MHz to 3.3 MHz
+    /// (2.3 MHz to 3.3 MHz for sk6812w devices). Up to 3.8 MHz, the low time
+    /// of a 1 bit is shorter than specified, which most devices still accept.
+    /// This is used if no other timing is configured.
+    pub const DEFAULT: Self = Self {
+        bits: 4,
+        zero_high: 1,
+        one_high: 3,
+        // Should be > 300μs, so for an SPI Freq. of 3.8MHz, we have to send at least 1140 low bits or 140 low bytes
+        reset_bytes: 140,
+    };
+
+    /// Find the shortest pattern meeting the chip timing at the given spi
+    /// frequency
+    ///
+    /// The reset time is set to the minimum reset time of the chip.
+    ///
+    /// Returns `None` if no pattern with 3, 4, 5, 6 or 8 spi bits per led bit
+    /// fits, or `spi_freq` is 0.
+    pub const fn new(spi_freq: u32, chip: &ChipTiming) -> Option<Self> {
+        if spi_freq == 0 {
+            return None;
+        }
+        let mut i = 0;
+        while i < SYMBOL_BITS.len() {
+            let bits = SYMBOL_BITS[i];
+            i += 1;
+            let bit_time = duration(bits, spi_freq);
+            if bit_time < chip.bit_min as u64 || bit_time > chip.bit_max as u64 {
+                continue;
+            }
+            // Leave enough low spi bits after the high time, so consecutive
+            // bits don't merge
+            let mut max_high = bits - 1;
+            while max_high > 0 && duration(bits - max_high, spi_freq) < chip.tl_min as u64 {
+                max_high -= 1;
+            }
+            let zero_high = best_fit(max_high, spi_freq, chip.t0h_min, chip.t0h_max);
+            let one_high = best_fit(max_high, spi_freq, chip.t1h_min, chip.t1h_max);
+            if zero_high != 0 && one_high > zero_high {
+                return Some(Self {
+                    bits,
+                    zero_high,
+                    one_high,
+                    reset_bytes: reset_bytes(spi_freq, chip.reset_min),
+                });
+            }
+        }
+        None
+    }
+
+    /// Find the shortest pattern meeting the timing of device `D` at the given
+    /// spi frequency
+    pub const fn for_device<D: Device>(spi_freq: u32) -> Option<Self> {
+        Self::new(spi_freq, &D::TIMING)
+    }
+
+    /// Use a custom pattern
+    ///
+    /// `bits` spi bits are used per led bit, of which the first `zero_high`
+    /// (or `one_high`) ones are high for a 0 (or 1) bit.
+    ///
+    /// Returns `None` if `bits` isn't 3, 4, 5, 6 or 8, or the high times are
+    /// out of order.
+    ///
+    /// The reset time is the same as for [`Timing::DEFAULT`], use
+    /// [`Timing::with_reset`] to change it.
+    pub const fn from_pattern(bits: u8, zero_high: u8, one_high: u8) -> Option<Self> {
+        let mut i = 0;
+        let mut supported = false;
+        while i < SYMBOL_BITS.len() {
+            supported |= SYMBOL_BITS[i] == bits;
+            i += 1;
+        }
+        if !supported || zero_high == 0 || one_high <= zero_high || one_high >= bits {
+            return None;
+        }
+        Some(Self {
+            bits,
+            zero_high,
+            one_high,
+            reset_bytes: Self::DEFAULT.reset_bytes,
+        })
+    }
+
+    /// Use a different reset time
+    ///
+    /// Sends enough low bytes at `spi_freq` to keep the data line low for at
+    /// least `reset` ns. See [`reset`] for the values of common devices.
+    pub const fn with_reset(mut self, spi_freq: u32, reset: u32) -> Self {
+        self.reset_bytes = reset_bytes(spi_freq, reset);
+        self
+    }
+
+    /// Use a fixed amount of low bytes as reset
+    pub const fn with_reset_bytes(mut self, reset_bytes: usize) -> Self {
+        self.reset_bytes = reset_bytes;
+        self
+    }
+
+    /// Amount of spi bits per led bit
+    ///
+    /// This is also the amount of spi bytes needed per byte of led data.
+    pub const fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Amount of low spi bytes sent after the data
+    pub const fn reset_bytes(&self) -> usize {
+        self.reset_bytes
+    }
+
+    /// Amount of high spi bits for a 0 bit
+    pub const fn zero_high(&self) -> u8 {
+        self.zero_high
+    }
+
+    /// Amount of high spi bits for a 1 bit
+    pub const fn one_high(&self) -> u8 {
+        self.one_high
+    }
+
+    /// The spi bits for a single led bit, right aligned
+    pub const fn symbol(&self, bit: bool) -> u8 {
+        let high = if bit { self.one_high } else { self.zero_high };
+        (((1u16 << high) - 1) << (self.bits - high)) as u8
+    }
+
+    /// Encode a single byte of led data into `out`, MSB first
+    ///
+    /// `out` has to be exactly [`Timing::bits`] bytes long.
+    pub(crate) fn encode(&self, data: u8, out: &mut [u8]) {
+        let mut symbols: u64 = 0;
+        for i in (0..8).rev() {
+            symbols = (symbols << self.bits) | self.symbol(data & (1 << i) != 0) as u64;
+        }
+        for (i, byte) in out.iter_mut().rev().enumerate() {
+            *byte = (symbols >> (8 * i)) as u8;
+        }
+    }
+
+    /// Decode a single byte of led data, which was encoded by
+    /// [`Timing::encode`]
+    ///
+    /// `data` has to be exactly [`Timing::bits`] bytes long. Each symbol is
+    /// sampled right after the high time of a 0 bit.
+    pub(crate) fn decode(&self, data: &[u8]) -> u8 {
+        let mut symbols: u64 = 0;
+        for byte in data {
+            symbols = (symbols << 8) | *byte as u64;
+        }
+        let bits = self.bits as u32;
+        let sample = bits - 1 - self.zero_high as u32;
+        let mut value = 0;
+        for i in (0..8).rev() {
+            value = (value << 1) | ((symbols >> (i * bits + sample)) & 1) as u8;
+        }
+        value
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Duration of `bits` spi bits in ns, saturated to `u32::MAX` for a stopped
+/// spi
+const fn duration(bits: u8, spi_freq: u32) -> u64 {
+    match (bits as u64 * 1_000_000_000).checked_div(spi_freq as u64) {
+        Some(time) if time < u32::MAX as u64 => time,
+        _ => u32::MAX as u64,
+    }
+}
+
+/// Amount of spi bytes needed to cover `time` ns
+const fn reset_bytes(spi_freq: u32, time: u32) -> usize {
+    let bits = (time as u64 * spi_freq as u64).div_ceil(1_000_000_000);
+    bits.div_ceil(8) as usize
+}
+
+/// Amount of high spi bits (up to `max_bits`) closest to the lower part of the
+/// allowed range, or 0 if none fits
+///
+/// The upper limits are often far from the typical values, e.g. 5μs for T1H
+/// of the ws2812, so a quarter into the range is used instead of the middle.
+const fn best_fit(max_bits: u8, spi_freq: u32, min: u32, max: u32) -> u8 {
+    // Only the part of the range reachable with `max_bits`
+    let longest = duration(max_bits, spi_freq);
+    let max = if longest < max as u64 {
+        longest
+    } else {
+        max as u64
+    };
+    let target = min as u64 + max.saturating_sub(min as u64) / 4;
+    let mut best = 0;
+    let mut best_distance = u64::MAX;
+    let mut high = 1;
+    while high <= max_bits {
+        let time = duration(high, spi_freq);
+        if time >= min as u64 && time <= max {
+            let distance = time.abs_diff(target);
+            if distance < best_distance {
+                best = high;
+                best_distance = distance;
+            }
+        }
+        high += 1;
+    }
+    best
+}
+
+/// A duration of the generated waveform, in ns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Measured {
+    pub actual: u32,
+    /// Distance to the closest limit, negative if out of spec
+    pub margin: i32,
+}
+
+impl Measured {
+    const fn new(actual: u64, min: u32, max: u32) -> Self {
+        let actual = actual as i64;
+        let to_min = actual - min as i64;
+        let to_max = max as i64 - actual;
+        let margin = if to_min < to_max { to_min } else { to_max };
+        Self {
+            actual: actual as u32,
+            // Saturated, durations are up to `u32::MAX`
+            margin: if margin < i32::MIN as i64 {
+                i32::MIN
+            } else if margin > i32::MAX as i64 {
+                i32::MAX
+            } else {
+                margin as i32
+            },
+        }
+    }
+
+    const fn in_spec(&self) -> bool {
+        self.margin >= 0
+    }
+}
+
+/// The generated waveform, compared to the chip timing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// High time of a 0 bit
+    pub t0h: Measured,
+    /// High time of a 1 bit
+    pub t1h: Measured,
+    /// Low time of a 1 bit, the shorter one
+    pub t1l: Measured,
+    /// Duration of a whole bit
+    pub bit: Measured,
+    /// Low time after the data
+    pub reset: Measured,
+}
+
+/// Part of the waveform, that doesn't meet the chip timing
+///
+/// Contains the full [`Report`] to see by how much.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimingError {
+    /// The high time of a 0 bit is out of spec
+    T0H(Report),
+    /// The high time of a 1 bit is out of spec
+    T1H(Report),
+    /// The low time of a 1 bit is too short, so consecutive bits may merge
+    T1L(Report),
+    /// The duration of a bit is out of spec
+    BitPeriod(Report),
+    /// The low time after the data is too short to latch it
+    Reset(Report),
+}
+
+impl core::fmt::Display for TimingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (name, measured) = match self {
+            Self::T0H(r) => ("T0H", r.t0h),
+            Self::T1H(r) => ("T1H", r.t1h),
+            Self::T1L(r) => ("T1L", r.t1l),
+            Self::BitPeriod(r) => ("bit period", r.bit),
+            Self::Reset(r) => ("reset time", r.reset),
+        };
+        write!(
+            f,
+            "{} of {} ns is {} ns out of spec",
+            name, measured.actual, -measured.margin
+        )
+    }
+}
+
+/// Check the waveform generated by `timing` at `spi_freq` against the timing
+/// of device `D`
+///
+/// This can be evaluated at compile time, see
+/// [`assert_timing!`](crate::assert_timing).
+pub const fn validate<D: Device>(spi_freq: u32, timing: &Timing) -> Result<Report, TimingError> {
+    let chip = &D::TIMING;
+    let report = Report {
+        t0h: Measured::new(
+            duration(timing.zero_high, spi_freq),
+            chip.t0h_min,
+            chip.t0h_max,
+        ),
+        t1h: Measured::new(
+            duration(timing.one_high, spi_freq),
+            chip.t1h_min,
+            chip.t1h_max,
+        ),
+        t1l: Measured::new(
+            duration(timing.bits - timing.one_high, spi_freq),
+            chip.tl_min,
+            u32::MAX,
+        ),
+        bit: Measured::new(duration(timing.bits, spi_freq), chip.bit_min, chip.bit_max),
+        reset: Measured::new(
+            // Not rounded per byte, to match `Timing::with_reset`
+            match (timing.reset_bytes as u64 * 8 * 1_000_000_000).checked_div(spi_freq as u64) {
+                Some(time) => time,
+                None => u32::MAX as u64,
+            },
+            chip.reset_min,
+            u32::MAX,
+        ),
+    };
+    if !report.t0h.in_spec() {
+        Err(TimingError::T0H(report))
+    } else if !report.t1h.in_spec() {
+        Err(TimingError::T1H(report))
+    } else if !report.t1l.in_spec() {
+        Err(TimingError::T1L(report))
+    } else if !report.bit.in_spec() {
+        Err(TimingError::BitPeriod(report))
+    } else if !report.reset.in_spec() {
+        Err(TimingError::Reset(report))
+    } else {
+        Ok(report)
+    }
+}
+
+/// Fail the build, if the waveform doesn't meet the timing of the device
+///
+/// Takes the device type, the spi frequency in Hz & the [`Timing`]:
+/// `assert_timing!(devices::Ws2812, 3_000_000, Timing::DEFAULT);`
+#[macro_export]
+macro_rules! assert_timing {
+    ($device:ty, $spi_freq:expr, $timing:expr) => {
+        const _: () = match $crate::timing::validate::<$device>($spi_freq, &$timing) {
+            Ok(_) => (),
+            Err($crate::timing::TimingError::T0H(_)) => {
+                panic!("T0H is out of spec for this spi frequency")
+            }
+            Err($crate::timing::TimingError::T1H(_)) => {
+                panic!("T1H is out of spec for this spi frequency")
+            }
+            Err($crate::timing::TimingError::T1L(_)) => {
+                panic!("T1L is too short for this spi frequency")
+            }
+            Err($crate::timing::TimingError::BitPeriod(_)) => {
+                panic!("Bit period is out of spec for this spi frequency")
+            }
+            Err($crate::timing::TimingError::Reset(_)) => {
+                panic!("Reset time is too short for this spi frequency")
+            }
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Low time of a 1 bit, in ns
+    fn t1l(timing: &Timing, spi_freq: u32) -> u64 {
+        duration(timing.bits() - timing.one_high(), spi_freq)
+    }
+
+    #[test]
+    fn new_picks_shortest_pattern() {
+        let timing = Timing::new(6_000_000, &ChipTiming::WS2812).unwrap();
+        assert_eq!(
+            (timing.bits(), timing.zero_high(), timing.one_high()),
+            (6, 1, 4)
+        );
+        assert_eq!(t1l(&timing, 6_000_000), 333);
+        let timing = Timing::new(8_000_000, &ChipTiming::WS2812).unwrap();
+        assert_eq!(
+            (timing.bits(), timing.zero_high(), timing.one_high()),
+            (8, 2, 5)
+        );
+        assert_eq!(t1l(&timing, 8_000_000), 375);
+        // No pattern is slow enough
+        assert_eq!(Timing::new(20_000_000, &ChipTiming::WS2812), None);
+    }
+
+    #[test]
+    fn new_leaves_low_time() {
+        let chips = [
+            ChipTiming::WS2812,
+            ChipTiming::WS2811,
+            ChipTiming::WS2811_SLOW,
+            ChipTiming::WS2813,
+            ChipTiming::WS2815,
+            ChipTiming::UCS8904,
+            ChipTiming::TM1814,
+            ChipTiming::APA106,
+            ChipTiming::SK6812,
+        ];
+        for chip in chips {
+            for spi_freq in (1..=100).map(|f| f * 200_000) {
+                if let Some(timing) = Timing::new(spi_freq, &chip) {
+                    assert!(t1l(&timing, spi_freq) >= chip.tl_min as u64);
+                    let t1h = duration(timing.one_high(), spi_freq);
+                    assert!(t1h >= chip.t1h_min as u64 && t1h <= chip.t1h_max as u64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_freq() {
+        assert_eq!(Timing::new(0, &ChipTiming::WS2812), None);
+        assert_eq!(Timing::for_device::<crate::devices::Ws2812>(0), None);
+        assert!(crate::encoder::Encoder::<crate::devices::Ws2812>::for_freq(0).is_none());
+        assert!(matches!(
+            validate::<crate::devices::Ws2812>(0, &Timing::DEFAULT),
+            Err(TimingError::T0H(_))
+        ));
+    }
+
+    #[test]
+    fn reset_bytes_cover_reset_time() {
+        // 280μs at 3 MHz are 840 bits
+        let timing = Timing::DEFAULT.with_reset(3_000_000, reset::WS2812B_V5);
+        assert_eq!(timing.reset_bytes(), 105);
+        // Rounded up to whole bits & bytes
+        let timing = Timing::DEFAULT.with_reset(3_000_000, reset::WS2812B_V5 + 1);
+        assert_eq!(timing.reset_bytes(), 106);
+        assert!(validate::<crate::devices::Ws2812>(3_000_000, &timing).is_ok());
+    }
+
+    #[test]
+    fn validate_low_time() {
+        // One low spi bit of 125ns
+        let timing = Timing::from_pattern(8, 2, 7)
+            .unwrap()
+            .with_reset(8_000_000, reset::WS2812B_V5);
+        match validate::<crate::devices::Ws2812>(8_000_000, &timing) {
+            Err(TimingError::T1L(report)) => {
+                assert_eq!(
+                    report.t1l,
+                    Measured {
+                        actual: 125,
+                        margin: -175
+                    }
+                );
+            }
+            result => panic!("{:?}", result),
+        }
+        let timing = Timing::for_device::<crate::devices::Ws2812>(8_000_000).unwrap();
+        assert!(validate::<crate::devices::Ws2812>(8_000_000, &timing).is_ok());
+        // The default pattern at 3.8 MHz leaves only 263ns
+        let timing = Timing::DEFAULT.with_reset(3_800_000, reset::WS2812B_V5);
+        assert!(matches!(
+            validate::<crate::devices::Ws2812>(3_800_000, &timing),
+            Err(TimingError::T1L(_))
+        ));
+        assert!(validate::<crate::devices::Ws2812>(3_300_000, &timing).is_ok());
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(Timing::DEFAULT.symbol(false), 0b1000);
+        assert_eq!(Timing::DEFAULT.symbol(true), 0b1110);
+        let timing = Timing::from_pattern(3, 1, 2).unwrap();
+        assert_eq!(timing.symbol(false), 0b100);
+        assert_eq!(timing.symbol(true), 0b110);
+    }
+
+    #[test]
+    fn encode_default() {
+        // Same bytes as the fixed patterns of the 2 led bits per spi byte
+        // used before
+        let mut out = [0; 4];
+        Timing::DEFAULT.encode(0xa5, &mut out);
+        assert_eq!(out, [0b1110_1000, 0b1110_1000, 0b1000_1110, 0b1000_1110]);
+        Timing::DEFAULT.encode(0x00, &mut out);
+        assert_eq!(out, [0b1000_1000; 4]);
+        Timing::DEFAULT.encode(0xff, &mut out);
+        assert_eq!(out, [0b1110_1110; 4]);
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        for bits in SYMBOL_BITS {
+            for zero_high in 1..bits - 1 {
+                for one_high in zero_high + 1..bits {
+                    let timing = Timing::from_pattern(bits, zero_high, one_high).unwrap();
+                    let mut out = [0; 8];
+                    let out = &mut out[..bits as usize];
+                    for data in 0..=255 {
+                        timing.encode(data, out);
+                        assert_eq!(timing.decode(out), data);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_pattern_rejects_invalid() {
+        assert_eq!(Timing::from_pattern(7, 2, 4), None);
+        assert_eq!(Timing::from_pattern(4, 0, 3), None);
+        assert_eq!(Timing::from_pattern(4, 3, 3), None);
+        assert_eq!(Timing::from_pattern(4, 1, 4), None);
+    }
+}
@@ -0,0 +1,345 @@
+//! Uart variant, for boards without a free spi
+//!
+//! Each uart frame carries 3 led bits, with the start & stop bits as part of
+//! the waveform. This needs the uart to be configured for 7 data bits, no
+//! parity & 1 stop bit (7N1) with inverted TX, so the line idles low. Every led
+//! bit is then 3 uart bits long: high, the value of the bit & low. For devices
+//! with inverted data (like the tm1814), TX must not be inverted.
+//!
+//! The uart should run within 2 Mbaud to 3 Mbaud for ws2812 devices, other
+//! devices can be checked with [`Ws2812::new_device`].
+//!
+//! The reset can't be sent as data, as every uart frame starts with a high
+//! pulse. Instead, the line needs to idle for the reset time (~300μs for newer
+//! ws2812 devices) between two writes.
+//!
+//! Anything implementing the embedded-io `Write` trait can be used with the
+//! `embedded_io` feature, the embedded-hal 0.2 `serial::Write` trait is
+//! supported with the `hal_02` feature by wrapping it in `Serial`.
+
+use core::marker::PhantomData;
+
+use smart_leds_trait::SmartLedsWrite;
+
+use crate::color_order::ColorOrder;
+pub use crate::devices;
+use crate::devices::Device;
+use crate::encoder::Encoder;
+use crate::timing::{self, Timing};
+use crate::Error;
+
+/// The uart frame for each combination of 3 led bits, first bit in the MSB
+///
+/// With inverted TX, the start bit is the high part of the first led bit & the
+/// stop bit the low part of the last one.
+const FRAMES: [u8; 8] = [0x5b, 0x1b, 0x53, 0x13, 0x5a, 0x1a, 0x52, 0x12];
+
+/// Blocking sink for the uart data generated by the driver
+pub trait Write {
+    type Error;
+
+    /// Write all bytes of `data`
+    ///
+    /// This may return before the data is completely sent out.
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Wait until all previously written data is sent out
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "embedded_io")]
+impl<UART> Write for UART
+where
+    UART: embedded_io::Write,
+{
+    type Error = UART::Error;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(data)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        embedded_io::Write::flush(self)
+    }
+}
+
+/// Use an embedded-hal 0.2 `serial::Write` uart
+#[cfg(feature = "hal_02")]
+pub struct Serial<UART>(pub UART);
+
+#[cfg(feature = "hal_02")]
+impl<UART, E> Write for Serial<UART>
+where
+    UART: embedded_hal_0_2::serial::Write<u8, Error = E>,
+{
+    type Error = E;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), E> {
+        use nb::block;
+        for b in data {
+            block!(self.0.write(*b))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), E> {
+        use nb::block;
+        block!(self.0.flush())
+    }
+}
+
+pub struct Ws2812<UART, DEVICE = devices::Ws2812>
+where
+    DEVICE: Device,
+{
+    uart: UART,
+    order: ColorOrder,
+    config: DEVICE::Config,
+    device: PhantomData<DEVICE>,
+}
+
+impl<UART, E> Ws2812<UART>
+where
+    UART: Write<Error = E>,
+{
+    /// Use ws2812 devices via uart
+    ///
+    /// The uart should run within 2 Mbaud to 3 Mbaud, with 7N1 frames &
+    /// inverted TX.
+    pub fn new(uart: UART) -> Self {
+        Self::with_device(uart)
+    }
+}
+
+impl<UART, E> Ws2812<UART, devices::Sk6812w>
+where
+    UART: Write<Error = E>,
+{
+    /// Use sk6812w devices via uart
+    ///
+    /// The uart should run within 2 Mbaud to 3 Mbaud, with 7N1 frames &
+    /// inverted TX.
+    pub fn new_sk6812w(uart: UART) -> Self {
+        Self::with_device(uart)
+    }
+}
+
+impl<UART, D, E> Ws2812<UART, D>
+where
+    UART: Write<Error = E>,
+    D: Device,
+{
+    /// The uart bit pattern of each led bit, checked against the device
+    const PATTERN: Option<Timing> = Timing::from_pattern(3, 1, 2);
+
+    /// Use any supported device via uart
+    ///
+    /// Returns `None`, if the device can't be driven at `baud_rate`.
+    pub fn new_device(uart: UART, baud_rate: u32) -> Option<Self> {
+        // The reset is the idle time between writes, which isn't checked
+        let pattern = Self::PATTERN?.with_reset(baud_rate, D::TIMING.reset_min);
+        timing::validate::<D>(baud_rate, &pattern).ok()?;
+        Some(Self::with_device(uart))
+    }
+
+    fn with_device(uart: UART) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Encoder::<D>::DEVICE_CHECK;
+        Self {
+            uart,
+            order: D::ORDER,
+            config: Default::default(),
+            device: PhantomData {},
+        }
+    }
+
+    /// Send the color channels in a different order
+    ///
+    /// By default, the channels are sent in the order of the device
+    /// ([`Device::ORDER`]).
+    pub fn with_color_order(mut self, order: ColorOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Use different device specific settings
+    ///
+    /// These are sent at the start of every frame, e.g. the drive currents
+    /// of [`devices::Tm1814`].
+    pub fn with_config(mut self, config: D::Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Change the device specific settings
+    pub fn set_config(&mut self, config: D::Config) {
+        self.config = config;
+    }
+}
+
+impl<UART, D, E> SmartLedsWrite for Ws2812<UART, D>
+where
+    UART: Write<Error = E>,
+    D: Device,
+{
+    type Error = Error<E>;
+    type Color = D::Color;
+    /// Write all the items of an iterator to a ws2812 strip
+    ///
+    /// If the amount of led bits isn't a multiple of 3, the last frame is
+    /// padded with 0 bits, which are ignored by the leds.
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Error<E>>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let mut packer = Packer::default();
+
+        let mut header = [0; devices::MAX_HEADER_BYTES];
+        let header = &mut header[..D::HEADER_BYTES];
+        D::write_header(&self.config, self.order, header);
+        packer.push(&mut self.uart, header)?;
+
+        let mut data = [0; devices::MAX_BYTES_PER_LED];
+        let data = &mut data[..D::BYTES_PER_LED];
+        for item in iterator {
+            D::write_led(item.into(), self.order, data);
+            packer.push(&mut self.uart, data)?;
+        }
+        packer.finish(&mut self.uart)?;
+        self.uart.flush().map_err(Error::Spi)
+    }
+}
+
+/// Collects led data in groups of 3 bytes, which fit into 8 uart frames
+#[derive(Default)]
+struct Packer {
+    data: [u8; 3],
+    len: usize,
+}
+
+impl Packer {
+    fn push<UART: Write>(&mut self, uart: &mut UART, data: &[u8]) -> Result<(), UART::Error> {
+        for byte in data {
+            self.data[self.len] = *byte;
+            self.len += 1;
+            if self.len == self.data.len() {
+                self.finish(uart)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the pending bytes, padded to whole uart frames
+    fn finish<UART: Write>(&mut self, uart: &mut UART) -> Result<(), UART::Error> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        let mut bits = 0u32;
+        for (i, byte) in self.data[..self.len].iter().enumerate() {
+            bits |= (*byte as u32) << (16 - 8 * i);
+        }
+        let mut out = [0; 8];
+        let frames = (self.len * 8).div_ceil(3);
+        for (i, out) in out[..frames].iter_mut().enumerate() {
+            *out = FRAMES[(bits >> (21 - 3 * i)) as usize & 0b111];
+        }
+        self.len = 0;
+        uart.write(&out[..frames])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Collects the written uart frames
+    #[derive(Default)]
+    struct Uart(Vec<u8>);
+
+    impl Write for Uart {
+        type Error = ();
+
+        fn write(&mut self, data: &[u8]) -> Result<(), ()> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    /// The line levels of a 7N1 frame with inverted TX: start bit, 7 data bits
+    /// (LSB first) & stop bit
+    fn line(frame: u8) -> [u8; 9] {
+        let mut line = [1, 0, 0, 0, 0, 0, 0, 0, 0];
+        for (i, level) in line[1..8].iter_mut().enumerate() {
+            *level = !(frame >> i) & 1;
+        }
+        line
+    }
+
+    /// Decode the led bits from the line levels, checking the high & low part
+    /// of each bit
+    fn decode(frames: &[u8]) -> Vec<u8> {
+        let mut bits = Vec::new();
+        for frame in frames {
+            for bit in line(*frame).chunks_exact(3) {
+                assert_eq!((bit[0], bit[2]), (1, 0));
+                bits.push(bit[1]);
+            }
+        }
+        bits
+    }
+
+    /// The bits of `data`, MSB first
+    fn bits(data: &[u8]) -> Vec<u8> {
+        data.iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+            .collect()
+    }
+
+    #[test]
+    fn frames() {
+        for (value, frame) in FRAMES.iter().enumerate() {
+            assert_eq!(
+                decode(&[*frame]),
+                [
+                    (value >> 2) as u8 & 1,
+                    (value >> 1) as u8 & 1,
+                    value as u8 & 1
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn new_device_baud_rates() {
+        assert!(Ws2812::<_>::new_device(Uart::default(), 2_500_000).is_some());
+        assert!(Ws2812::<_>::new_device(Uart::default(), 4_000_000).is_none());
+        assert!(Ws2812::<_>::new_device(Uart::default(), 0).is_none());
+    }
+
+    #[test]
+    fn packer() {
+        let data = [0xa5, 0x3c, 0xff, 0x01, 0x80];
+        // Whole groups of 3 bytes & the padded last group of 1 or 2 bytes
+        for (len, frames) in [(1, 3), (2, 6), (3, 8), (4, 11), (5, 14)] {
+            let mut uart = Uart::default();
+            let mut packer = Packer::default();
+            packer.push(&mut uart, &data[..len]).unwrap();
+            packer.finish(&mut uart).unwrap();
+            assert_eq!(uart.0.len(), frames);
+
+            let decoded = decode(&uart.0);
+            let (decoded, padding) = decoded.split_at(len * 8);
+            assert_eq!(decoded, bits(&data[..len]));
+            assert!(padding.iter().all(|bit| *bit == 0));
+        }
+    }
+}